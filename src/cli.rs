@@ -5,5 +5,7 @@ use std::path::PathBuf;
 #[command(version, about, long_about = None)]
 pub struct Cli {
     pub db_path: PathBuf,
-    pub command: String,
+    /// A dot-command or SQL statement to run. Without it, commands are read
+    /// from the standard input.
+    pub command: Option<String>,
 }
@@ -0,0 +1,393 @@
+use super::{
+    cell::{local_payload_size, Pager, RowId},
+    cursor::{child_page, lower_bound},
+    err,
+    file_header::FILE_HEADER_SIZE,
+    page::PageType,
+    utils,
+    varint::Varint,
+    Db, Page, PageNum, Result,
+};
+use std::io::{Read, Seek, Write};
+
+/// The content of a b-tree page as raw cells, which is rewritten as a whole
+/// when a cell is inserted and split over several pages when it no longer fits.
+#[derive(Debug)]
+struct Node {
+    r#type: PageType,
+    cells: Vec<Vec<u8>>,
+    right_most: Option<PageNum>,
+}
+
+impl Node {
+    fn empty(r#type: PageType) -> Self {
+        Self {
+            r#type,
+            cells: vec![],
+            right_most: None,
+        }
+    }
+
+    fn read(page: &mut Page, usable_size: usize) -> Result<Self> {
+        let r#type = page.page_type()?;
+        let cells = (0..page.num_cells()?)
+            .map(|idx| page.raw_cell(idx, usable_size))
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+        let right_most = if r#type.is_leaf() {
+            None
+        } else {
+            Some(page.right_most_pointer()?)
+        };
+
+        Ok(Self {
+            r#type,
+            cells,
+            right_most,
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.r#type.header_size() + self.cells.iter().map(|c| c.len() + 2).sum::<usize>()
+    }
+
+    /// Lays out the page with the cell pointer array after the header and the
+    /// cells packed at the end of the usable space.
+    fn write_to(&self, buf: &mut [u8], header_offset: usize, usable_size: usize) -> Result<()> {
+        if header_offset + self.size() > usable_size {
+            return Err(err!("Cells do not fit in the page"));
+        }
+
+        buf[header_offset..usable_size].fill(0);
+        let mut content_start = usable_size;
+        let mut pointer = header_offset + self.r#type.header_size();
+
+        for cell in &self.cells {
+            content_start -= cell.len();
+            buf[content_start..content_start + cell.len()].copy_from_slice(cell);
+            buf[pointer..pointer + 2].copy_from_slice(&(content_start as u16).to_be_bytes());
+            pointer += 2;
+        }
+
+        let header = &mut buf[header_offset..];
+        header[0] = self.r#type.byte();
+        header[3..5].copy_from_slice(&(self.cells.len() as u16).to_be_bytes());
+        // A content area starting at 65536 is stored as 0.
+        header[5..7].copy_from_slice(&(content_start as u16).to_be_bytes());
+        if let Some(right_most) = self.right_most {
+            header[8..12].copy_from_slice(&right_most.to_be_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Distributes the cells over as many pages as needed, each paired with
+    /// the key of its divider cell in the parent except for the last one.
+    ///
+    /// When a row was appended to the right most leaf, the new cell goes to a
+    /// page of its own so that tables filled in rowid order end up with full
+    /// pages.
+    fn split(self, capacity: usize, append: bool) -> Result<Vec<(Node, Option<RowId>)>> {
+        let is_leaf = self.r#type.is_leaf();
+        let header_size = self.r#type.header_size();
+        let total: usize = self.cells.iter().map(|c| c.len() + 2).sum();
+        let target = if append && is_leaf {
+            total - self.cells.last().map_or(0, |c| c.len() + 2)
+        } else {
+            let pages = total.div_ceil(capacity - header_size).max(2);
+            total.div_ceil(pages)
+        };
+
+        let mut parts = vec![];
+        let mut current = Node::empty(self.r#type);
+        let mut used = 0;
+
+        for cell in self.cells {
+            let full = used >= target || header_size + used + cell.len() + 2 > capacity;
+            if full && !current.cells.is_empty() {
+                let next = Node::empty(self.r#type);
+                if is_leaf {
+                    let key = cell_rowid(self.r#type, current.cells.last().unwrap())?;
+                    parts.push((std::mem::replace(&mut current, next), Some(key)));
+                    used = 0;
+                } else {
+                    // An interior page is closed by moving the cell up to the
+                    // parent, its left child becoming the right most pointer.
+                    let key = cell_rowid(self.r#type, &cell)?;
+                    current.right_most = Some(left_child(&cell));
+                    parts.push((std::mem::replace(&mut current, next), Some(key)));
+                    used = 0;
+                    continue;
+                }
+            }
+            used += cell.len() + 2;
+            current.cells.push(cell);
+        }
+
+        if current.cells.is_empty() {
+            return Err(err!("Cannot split a page into an empty page"));
+        }
+        current.right_most = self.right_most;
+        parts.push((current, None));
+
+        Ok(parts)
+    }
+}
+
+impl<R: Read + Write + Seek> Db<R> {
+    /// Inserts a record into the table b-tree with the given root page,
+    /// splitting pages up to the root when the leaf is full.
+    pub(super) fn insert_record(
+        &self,
+        rootpage: PageNum,
+        rowid: RowId,
+        record: &[u8],
+    ) -> Result<()> {
+        let cell = self.leaf_table_cell(rowid, record)?;
+
+        let mut path = vec![];
+        let mut num = rootpage;
+        let mut append = true;
+        let (mut page, idx) = loop {
+            let mut page = self.page(num)?;
+            let idx = lower_bound(&mut page, self, rowid)?;
+            append &= idx == page.num_cells()?;
+            if page.is_leaf()? {
+                break (page, idx);
+            }
+            path.push((num, idx));
+            num = child_page(&mut page, idx, self)?;
+        };
+
+        let mut node = Node::read(&mut page, self.usable_size()?)?;
+        node.cells.insert(idx, cell);
+        self.balance(rootpage, num, node, path, append)
+    }
+
+    /// Writes the node to its page, or splits it and inserts the dividers into
+    /// the parent, continuing upwards while the parent overflows. A full root
+    /// keeps its page number and moves its content into new children.
+    fn balance(
+        &self,
+        rootpage: PageNum,
+        mut num: PageNum,
+        mut node: Node,
+        mut path: Vec<(PageNum, usize)>,
+        append: bool,
+    ) -> Result<()> {
+        let usable_size = self.usable_size()?;
+
+        loop {
+            if header_offset(num) + node.size() <= usable_size {
+                return self.write_node(num, &node);
+            }
+
+            let mut parts = node.split(usable_size, append)?;
+            let (last, _) = parts.pop().ok_or(err!("Splitting a page gave no pages"))?;
+
+            let mut dividers = vec![];
+            for (part, key) in parts {
+                let key = key.ok_or(err!("Split page has no divider key"))?;
+                let page = self.allocate_page()?;
+                self.write_node(page, &part)?;
+                dividers.push(interior_table_cell(page, key));
+            }
+
+            match path.pop() {
+                Some((parent, idx)) => {
+                    self.write_node(num, &last)?;
+                    let mut parent_node = Node::read(&mut self.page(parent)?, usable_size)?;
+                    parent_node.cells.splice(idx..idx, dividers);
+                    (num, node) = (parent, parent_node);
+                }
+                None if num == rootpage => {
+                    let right = self.allocate_page()?;
+                    self.write_node(right, &last)?;
+                    node = Node {
+                        r#type: PageType::InteriorTable,
+                        cells: dividers,
+                        right_most: Some(right),
+                    };
+                }
+                None => return Err(err!("Lost the path to the root page {rootpage}")),
+            }
+        }
+    }
+
+    /// Builds a table leaf cell, writing the part of the record that does not
+    /// fit on the page to overflow pages.
+    fn leaf_table_cell(&self, rowid: RowId, record: &[u8]) -> Result<Vec<u8>> {
+        let usable_size = self.usable_size()?;
+        let local_size = local_payload_size(PageType::LeafTable, record.len(), usable_size);
+
+        let mut cell = Varint::encode(record.len() as u64);
+        cell.extend(Varint::encode(rowid));
+        cell.extend(&record[..local_size]);
+
+        if local_size < record.len() {
+            let chunks: Vec<&[u8]> = record[local_size..].chunks(usable_size - 4).collect();
+            let pages = chunks
+                .iter()
+                .map(|_| self.allocate_page())
+                .collect::<Result<Vec<PageNum>>>()?;
+
+            for (i, chunk) in chunks.iter().enumerate() {
+                let next = pages.get(i + 1).copied().unwrap_or(0);
+                let mut buf = vec![0u8; self.file_header()?.page_size() as usize];
+                buf[..4].copy_from_slice(&next.to_be_bytes());
+                buf[4..4 + chunk.len()].copy_from_slice(chunk);
+                self.write_page(pages[i], buf)?;
+            }
+            cell.extend(pages[0].to_be_bytes());
+        }
+
+        Ok(cell)
+    }
+
+    fn write_node(&self, num: PageNum, node: &Node) -> Result<()> {
+        let page_size = self.file_header()?.page_size() as usize;
+        let mut buf = vec![0u8; page_size];
+
+        let offset = header_offset(num);
+        if offset > 0 {
+            let page = self.page_buffer(num)?;
+            buf[..offset].copy_from_slice(&page.as_ref()[..offset]);
+        }
+
+        node.write_to(&mut buf, offset, self.usable_size()?)?;
+        self.write_page(num, buf)
+    }
+}
+
+#[cfg(test)]
+impl Db<std::io::Cursor<Vec<u8>>> {
+    /// A copy of the sample database in memory with another table, created by
+    /// the statement on a new page and holding the rows with rowids from 1.
+    pub(crate) fn sample_with_table(
+        name: &str,
+        sql: &str,
+        rows: &[Vec<super::RecordValue>],
+    ) -> Result<Self> {
+        use super::{cell::Record, RecordValue};
+
+        let db = super::test_utils::sample_db(&[]);
+        let rootpage = db.allocate_page()?;
+        db.write_node(rootpage, &Node::empty(PageType::LeafTable))?;
+
+        let encoding = db.text_encoding()?;
+        let schema = [
+            RecordValue::Text("table".into()),
+            RecordValue::Text(name.into()),
+            RecordValue::Text(name.into()),
+            RecordValue::Int(rootpage.into()),
+            RecordValue::Text(sql.into()),
+        ];
+        let rowid = db.schemas()?.count() as RowId + 1;
+        db.insert_record(1, rowid, &Record::encode(&schema, encoding))?;
+        for (rowid, row) in (1..).zip(rows) {
+            db.insert_record(rootpage, rowid, &Record::encode(row, encoding))?;
+        }
+        Ok(db)
+    }
+}
+
+/// The first page holds the file header before its b-tree page header.
+fn header_offset(num: PageNum) -> usize {
+    if num == 1 {
+        FILE_HEADER_SIZE
+    } else {
+        0
+    }
+}
+
+fn interior_table_cell(left: PageNum, rowid: RowId) -> Vec<u8> {
+    let mut cell = left.to_be_bytes().to_vec();
+    cell.extend(Varint::encode(rowid));
+    cell
+}
+
+fn left_child(cell: &[u8]) -> PageNum {
+    u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]])
+}
+
+fn cell_rowid(r#type: PageType, cell: &[u8]) -> Result<RowId> {
+    let mut r = cell;
+    match r#type {
+        PageType::LeafTable => {
+            Varint::new(&mut r)?;
+        }
+        PageType::InteriorTable => {
+            utils::read_4_bytes(&mut r)?;
+        }
+        _ => return Err(err!("Index b-tree cells have no rowid")),
+    }
+    Ok(Varint::new(&mut r)?.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{cell::Record, file_header::TextEncoding, test_utils::sample_db, RecordValue};
+
+    fn rowids<R: Read + Seek>(db: &Db<R>, rootpage: PageNum) -> Vec<RowId> {
+        let mut cursor = crate::db::cursor::BtreeCursor::new(db, rootpage).unwrap();
+        let mut rowids = vec![];
+        while let Some(cell) = cursor.next_cell().unwrap() {
+            rowids.push(cell.rowid().unwrap());
+        }
+        rowids
+    }
+
+    fn record(text: &str) -> Vec<u8> {
+        let values = [
+            RecordValue::Null,
+            RecordValue::Text(text.into()),
+            RecordValue::Null,
+        ];
+        Record::encode(&values, TextEncoding::Utf8)
+    }
+
+    #[test]
+    fn it_inserts_into_a_leaf() {
+        let db = sample_db(&[]);
+        let pages = db.file_header().unwrap().page_count();
+
+        db.insert_record(4, 10, &record("kumquat")).unwrap();
+        db.insert_record(4, 0, &record("yuzu")).unwrap();
+
+        assert_eq!(rowids(&db, 4), vec![0, 1, 2, 3, 4, 5, 6, 10]);
+        assert_eq!(db.file_header().unwrap().page_count(), pages);
+    }
+
+    #[test]
+    fn it_splits_pages_up_to_the_root() {
+        let db = sample_db(&[]);
+        let text = "x".repeat(900);
+
+        // Rows in reverse and then in rowid order to split both ways, with
+        // enough leaves for the interior pages to split as well.
+        let mut expected: Vec<RowId> = (1..=6).collect();
+        for rowid in (100..1600).rev().chain(5000..6500) {
+            db.insert_record(4, rowid, &record(&text)).unwrap();
+        }
+        expected.extend((100..1600).chain(5000..6500));
+        assert_eq!(rowids(&db, 4), expected);
+
+        let mut root = db.page(4).unwrap();
+        assert!(!root.is_leaf().unwrap());
+        let child = child_page(&mut root, 0, &db).unwrap();
+        assert!(!db.page(child).unwrap().is_leaf().unwrap());
+    }
+
+    #[test]
+    fn it_writes_overflow_pages() {
+        let db = sample_db(&[]);
+        let text = "y".repeat(10_000);
+
+        db.insert_record(4, 7, &record(&text)).unwrap();
+
+        let mut cursor = crate::db::cursor::BtreeCursor::new(&db, 4).unwrap();
+        cursor.seek(7).unwrap();
+        let cell = cursor.next_cell().unwrap().unwrap();
+        assert_eq!(cell.column(1).unwrap().to_string(), text);
+    }
+}
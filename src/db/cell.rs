@@ -1,9 +1,24 @@
-use super::{page::PageType, utils, varint::Varint, PageNum, Result};
-use std::fmt;
+use super::{
+    file_header::TextEncoding, page::PageType, utils, varint::Varint, Error, PageNum, Result,
+};
 use std::io::{Cursor, Read, Seek};
+use std::{cmp::Ordering, fmt};
 
 pub type RowId = u64;
 
+/// Access to the database file beyond the page a cell lives on, which is needed
+/// when a cell's payload spills onto overflow pages.
+pub trait Pager {
+    /// Page size minus the reserved space at the end of each page.
+    fn usable_size(&self) -> Result<usize>;
+
+    /// Reads `size` bytes from the overflow chain starting at page `first`.
+    fn overflow(&self, first: PageNum, size: usize) -> Result<Vec<u8>>;
+
+    /// Encoding of the text values in records.
+    fn text_encoding(&self) -> Result<TextEncoding>;
+}
+
 #[derive(Debug)]
 pub enum Cell {
     InteriorIndex { left: PageNum, payload: Record },
@@ -13,20 +28,21 @@ pub enum Cell {
 }
 
 impl Cell {
-    pub fn new<R: Read + Seek>(r#type: PageType, r: &mut R) -> Result<Self> {
+    pub fn new<R: Read + Seek, P: Pager>(r#type: PageType, r: &mut R, pager: &P) -> Result<Self> {
         match r#type {
-            PageType::InteriorIndex => Self::interior_index(r),
+            PageType::InteriorIndex => Self::interior_index(r, pager),
             PageType::InteriorTable => Self::interior_table(r),
-            PageType::LeafIndex => Self::leaf_index(r),
-            PageType::LeafTable => Self::leaf_table(r),
+            PageType::LeafIndex => Self::leaf_index(r, pager),
+            PageType::LeafTable => Self::leaf_table(r, pager),
         }
     }
 
     pub fn column(&self, num: usize) -> Option<RecordValue> {
-        if let Self::LeafTable { payload, .. } = self {
-            payload.column(num)
-        } else {
-            None
+        match self {
+            Self::LeafTable { payload, .. }
+            | Self::InteriorIndex { payload, .. }
+            | Self::LeafIndex { payload } => payload.column(num),
+            Self::InteriorTable { .. } => None,
         }
     }
 
@@ -44,31 +60,33 @@ impl Cell {
         }
     }
 
-    pub fn index_payload(&self) -> Option<(RecordValue, RowId)> {
+    /// The values of an index entry, which are the columns of a row in the
+    /// index storing a `WITHOUT ROWID` table.
+    pub fn keys(&self) -> Option<&[RecordValue]> {
         match self {
-            Self::InteriorIndex { payload, .. } | Self::LeafIndex { payload } => {
-                let key = payload.column(0);
-                let rowid = payload.column(1).and_then(|v| {
-                    if let RecordValue::Int(n) = v {
-                        n.try_into().ok()
-                    } else {
-                        None
-                    }
-                });
-                key.zip(rowid)
-            }
+            Self::InteriorIndex { payload, .. } | Self::LeafIndex { payload } => Some(&payload.0),
+            _ => None,
+        }
+    }
+
+    /// The key columns of an index entry, followed in the record by the rowid
+    /// of the row it points to.
+    pub fn index_payload(&self) -> Option<(&[RecordValue], RowId)> {
+        let (rowid, keys) = self.keys()?.split_last()?;
+        match rowid {
+            RecordValue::Int(n) => Some((keys, RowId::try_from(*n).ok()?)),
             _ => None,
         }
     }
 
-    fn interior_index<R: Read + Seek>(r: &mut R) -> Result<Self> {
+    fn interior_index<R: Read + Seek, P: Pager>(r: &mut R, pager: &P) -> Result<Self> {
         let left = u32::from_be_bytes(utils::read_4_bytes(r)?);
         let size = Varint::new(r)?.value() as usize;
-        let bytes = utils::read_n_bytes(r, size)?;
+        let bytes = read_payload(r, PageType::InteriorIndex, size, pager)?;
 
         Ok(Self::InteriorIndex {
             left,
-            payload: Record::new(bytes)?,
+            payload: Record::new(bytes, pager.text_encoding()?)?,
         })
     }
 
@@ -79,48 +97,123 @@ impl Cell {
         Ok(Self::InteriorTable { left, rowid })
     }
 
-    fn leaf_index<R: Read + Seek>(r: &mut R) -> Result<Self> {
+    fn leaf_index<R: Read + Seek, P: Pager>(r: &mut R, pager: &P) -> Result<Self> {
         let size = Varint::new(r)?.value() as usize;
-        let bytes = utils::read_n_bytes(r, size)?;
+        let bytes = read_payload(r, PageType::LeafIndex, size, pager)?;
 
         Ok(Self::LeafIndex {
-            payload: Record::new(bytes)?,
+            payload: Record::new(bytes, pager.text_encoding()?)?,
         })
     }
 
-    fn leaf_table<R: Read + Seek>(r: &mut R) -> Result<Self> {
+    fn leaf_table<R: Read + Seek, P: Pager>(r: &mut R, pager: &P) -> Result<Self> {
         let size = Varint::new(r)?.value() as usize;
         let rowid = Varint::new(r)?.value();
-        let bytes = utils::read_n_bytes(r, size)?;
+        let bytes = read_payload(r, PageType::LeafTable, size, pager)?;
 
         Ok(Self::LeafTable {
             rowid,
-            payload: Record::new(bytes)?,
+            payload: Record::new(bytes, pager.text_encoding()?)?,
         })
     }
 }
 
+/// Number of bytes a cell takes on its page, including the pointer to the first
+/// overflow page if the payload does not fit.
+pub fn cell_size(r#type: PageType, bytes: &[u8], usable_size: usize) -> Result<usize> {
+    let mut r = Cursor::new(bytes);
+
+    if let PageType::InteriorIndex | PageType::InteriorTable = r#type {
+        utils::read_4_bytes(&mut r)?;
+    }
+    if let PageType::InteriorTable = r#type {
+        Varint::new(&mut r)?;
+        return Ok(r.position() as usize);
+    }
+
+    let size = Varint::new(&mut r)?.value() as usize;
+    if let PageType::LeafTable = r#type {
+        Varint::new(&mut r)?;
+    }
+
+    let local_size = local_payload_size(r#type, size, usable_size);
+    let pointer_size = if local_size < size { 4 } else { 0 };
+    Ok(r.position() as usize + local_size + pointer_size)
+}
+
+/// Reads a payload of `size` bytes, following the overflow chain when only part
+/// of it is stored on the page itself.
+fn read_payload<R: Read, P: Pager>(
+    r: &mut R,
+    r#type: PageType,
+    size: usize,
+    pager: &P,
+) -> Result<Vec<u8>> {
+    let local_size = local_payload_size(r#type, size, pager.usable_size()?);
+    let mut bytes = utils::read_n_bytes(r, local_size)?;
+
+    if local_size < size {
+        let first = u32::from_be_bytes(utils::read_4_bytes(r)?);
+        bytes.extend(pager.overflow(first, size - local_size)?);
+    }
+
+    Ok(bytes)
+}
+
+/// Number of payload bytes stored on the b-tree page, as defined in the
+/// "Cell Payload Overflow Pages" section of the file format.
+pub fn local_payload_size(r#type: PageType, size: usize, usable_size: usize) -> usize {
+    let max_local = match r#type {
+        PageType::LeafTable => usable_size - 35,
+        _ => (usable_size - 12) * 64 / 255 - 23,
+    };
+
+    if size <= max_local {
+        return size;
+    }
+
+    let min_local = (usable_size - 12) * 32 / 255 - 23;
+    let local = min_local + (size - min_local) % (usable_size - 4);
+
+    if local <= max_local {
+        local
+    } else {
+        min_local
+    }
+}
+
 #[derive(Debug)]
 pub struct Record(Vec<RecordValue>);
 
 impl Record {
-    fn new(bytes: Vec<u8>) -> Result<Self> {
+    fn new(bytes: Vec<u8>, encoding: TextEncoding) -> Result<Self> {
+        let len = bytes.len();
         let mut cursor = Cursor::new(bytes);
 
         let mut headers: Vec<SerialType> = vec![];
         let header_size = Varint::new(&mut cursor)?;
         let mut bytes_read = header_size.byte_len();
+        let mut body_size = 0;
 
         while bytes_read < header_size.value() as usize {
             let v = Varint::new(&mut cursor)?;
             bytes_read += v.byte_len();
-            headers.push(SerialType::new(v.value()));
+            let header = SerialType::new(v.value())?;
+            body_size += header.size();
+            headers.push(header);
+        }
+
+        // Checked up front so that a corrupt size never allocates a huge value.
+        if bytes_read.saturating_add(body_size) > len {
+            return Err(Error::corrupt(format!(
+                "record of {len} bytes has a header of {bytes_read} bytes and values of {body_size} bytes"
+            )));
         }
 
         let mut values: Vec<RecordValue> = vec![];
 
         for header in headers {
-            let value = RecordValue::new(header, &mut cursor)?;
+            let value = RecordValue::new(header, &mut cursor, encoding)?;
             values.push(value);
         }
 
@@ -130,6 +223,30 @@ impl Record {
     fn column(&self, num: usize) -> Option<RecordValue> {
         self.0.get(num).cloned()
     }
+
+    /// Serializes values in the record format: a header of serial types
+    /// followed by the values themselves, with text in the given encoding.
+    pub fn encode(values: &[RecordValue], encoding: TextEncoding) -> Vec<u8> {
+        let mut types: Vec<u8> = vec![];
+        let mut body: Vec<u8> = vec![];
+
+        for value in values {
+            let (serial_type, bytes) = value.serialize(encoding);
+            types.extend(Varint::encode(serial_type));
+            body.extend(bytes);
+        }
+
+        // The header size counts its own varint as well.
+        let mut header_size = types.len() + 1;
+        while types.len() + Varint::encode(header_size as u64).len() > header_size {
+            header_size += 1;
+        }
+
+        let mut bytes = Varint::encode(header_size as u64);
+        bytes.extend(types);
+        bytes.extend(body);
+        bytes
+    }
 }
 
 #[derive(Debug)]
@@ -149,21 +266,37 @@ pub enum SerialType {
 }
 
 impl SerialType {
-    fn new(num: u64) -> Self {
+    /// Reads a serial type, where 10 and 11 are reserved and never appear in
+    /// a well-formed database.
+    fn new(num: u64) -> Result<Self> {
         match num {
-            0 => Self::Null,
-            1 => Self::TwosComplement8,
-            2 => Self::TwosComplement16,
-            3 => Self::TwosComplement24,
-            4 => Self::TwosComplement32,
-            5 => Self::TwosComplement48,
-            6 => Self::TwosComplement64,
-            7 => Self::Float,
-            8 => Self::Zero,
-            9 => Self::One,
-            n if n % 2 == 0 && n >= 12 => Self::Blob((n as usize - 12) / 2),
-            n if n % 2 == 1 && n >= 13 => Self::Text((n as usize - 13) / 2),
-            _ => panic!("Invalid serial type: {num}"),
+            0 => Ok(Self::Null),
+            1 => Ok(Self::TwosComplement8),
+            2 => Ok(Self::TwosComplement16),
+            3 => Ok(Self::TwosComplement24),
+            4 => Ok(Self::TwosComplement32),
+            5 => Ok(Self::TwosComplement48),
+            6 => Ok(Self::TwosComplement64),
+            7 => Ok(Self::Float),
+            8 => Ok(Self::Zero),
+            9 => Ok(Self::One),
+            n if n % 2 == 0 && n >= 12 => Ok(Self::Blob(((n - 12) / 2) as usize)),
+            n if n % 2 == 1 && n >= 13 => Ok(Self::Text(((n - 13) / 2) as usize)),
+            _ => Err(Error::corrupt(format!("invalid serial type {num}"))),
+        }
+    }
+
+    /// Number of bytes the value takes in the record body.
+    fn size(&self) -> usize {
+        match self {
+            Self::Null | Self::Zero | Self::One => 0,
+            Self::TwosComplement8 => 1,
+            Self::TwosComplement16 => 2,
+            Self::TwosComplement24 => 3,
+            Self::TwosComplement32 => 4,
+            Self::TwosComplement48 => 6,
+            Self::TwosComplement64 | Self::Float => 8,
+            Self::Blob(n) | Self::Text(n) => *n,
         }
     }
 }
@@ -179,7 +312,7 @@ pub enum RecordValue {
 }
 
 impl RecordValue {
-    fn new<R: Read>(r#type: SerialType, r: &mut R) -> Result<Self> {
+    fn new<R: Read>(r#type: SerialType, r: &mut R, encoding: TextEncoding) -> Result<Self> {
         match r#type {
             SerialType::Null => Ok(Self::Null),
             SerialType::TwosComplement8 => {
@@ -203,8 +336,10 @@ impl RecordValue {
                 Ok(Self::Int(val as i64))
             }
             SerialType::TwosComplement48 => {
-                let _bytes = utils::read_6_bytes(r)?;
-                unimplemented!()
+                let [b0, b1, b2, b3, b4, b5] = utils::read_6_bytes(r)?;
+                // Shifting back keeps the sign of the 48-bit value.
+                let val = i64::from_be_bytes([b0, b1, b2, b3, b4, b5, 0, 0]) >> 16;
+                Ok(Self::Int(val))
             }
             SerialType::TwosComplement64 => {
                 let bytes = utils::read_8_bytes(r)?;
@@ -224,25 +359,237 @@ impl RecordValue {
             }
             SerialType::Text(n) => {
                 let buf = utils::read_n_bytes(r, n)?;
-                Ok(Self::Text(String::from_utf8(buf)?))
+                Ok(Self::Text(encoding.decode(buf)?))
             }
         }
     }
 }
 
+impl RecordValue {
+    /// Serial type and content bytes of the value in a record.
+    fn serialize(&self, encoding: TextEncoding) -> (u64, Vec<u8>) {
+        match self {
+            Self::Null => (0, vec![]),
+            Self::Int(0) => (8, vec![]),
+            Self::Int(1) => (9, vec![]),
+            Self::Int(n) => serialize_int(*n),
+            Self::PrimaryKey(n) => serialize_int(*n as i64),
+            Self::Float(n) => (7, n.to_be_bytes().to_vec()),
+            Self::Blob(bytes) => (bytes.len() as u64 * 2 + 12, bytes.clone()),
+            Self::Text(t) => {
+                let bytes = encoding.encode(t);
+                (bytes.len() as u64 * 2 + 13, bytes)
+            }
+        }
+    }
+}
+
+/// Stores an integer in the fewest bytes that hold it.
+fn serialize_int(n: i64) -> (u64, Vec<u8>) {
+    let bytes = n.to_be_bytes();
+    let (serial_type, len) = match n {
+        n if i8::try_from(n).is_ok() => (1, 1),
+        n if i16::try_from(n).is_ok() => (2, 2),
+        -0x80_0000..=0x7f_ffff => (3, 3),
+        n if i32::try_from(n).is_ok() => (4, 4),
+        -0x8000_0000_0000..=0x7fff_ffff_ffff => (5, 6),
+        _ => (6, 8),
+    };
+    (serial_type, bytes[8 - len..].to_vec())
+}
+
 impl fmt::Display for RecordValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::PrimaryKey(n) => write!(f, "{n}"),
             Self::Null => write!(f, "NULL"),
             Self::Int(n) => write!(f, "{n}"),
-            Self::Float(n) => write!(f, "{n}"),
+            Self::Float(n) => write!(f, "{}", format_float(*n)),
             Self::Blob(bytes) => write!(f, "{bytes:?}"),
             Self::Text(t) => write!(f, "{t}"),
         }
     }
 }
 
+/// Formats a float the way SQLite prints it, with up to 15 significant digits
+/// and always showing a decimal point.
+fn format_float(n: f64) -> String {
+    if n.is_infinite() {
+        return if n > 0.0 { "Inf" } else { "-Inf" }.into();
+    }
+
+    let scientific = format!("{n:.14e}");
+    let (mantissa, exp) = scientific
+        .split_once('e')
+        .expect("Scientific notation always has an exponent");
+    let exp: i32 = exp.parse().expect("Exponent is always an integer");
+
+    if (-4..15).contains(&exp) {
+        let decimals = (14 - exp) as usize;
+        with_decimal_point(&format!("{n:.decimals$}"))
+    } else {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{sign}{:02}", with_decimal_point(mantissa), exp.abs())
+    }
+}
+
+fn with_decimal_point(num: &str) -> String {
+    let trimmed = num.trim_end_matches('0');
+    if trimmed.ends_with('.') {
+        format!("{trimmed}0")
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// The type a column prefers for its values, from its declared type as
+/// described in "Determination Of Column Affinity". `Blob` is no affinity at
+/// all.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Affinity {
+    Text,
+    Numeric,
+    Integer,
+    Real,
+    Blob,
+}
+
+impl Affinity {
+    pub fn new(declared_type: &str) -> Self {
+        let r#type = declared_type.to_uppercase();
+        let has = |name: &str| r#type.contains(name);
+
+        if has("INT") {
+            Self::Integer
+        } else if has("CHAR") || has("CLOB") || has("TEXT") {
+            Self::Text
+        } else if has("BLOB") || r#type.trim().is_empty() {
+            Self::Blob
+        } else if has("REAL") || has("FLOA") || has("DOUB") {
+            Self::Real
+        } else {
+            Self::Numeric
+        }
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Numeric | Self::Integer | Self::Real)
+    }
+}
+
+impl RecordValue {
+    /// Converts the value to the type the affinity prefers when that loses
+    /// nothing: text that looks like a number becomes one for the numeric
+    /// affinities, and numbers become text for the text affinity.
+    pub fn apply_affinity(self, affinity: Affinity) -> Self {
+        match (affinity, self) {
+            (Affinity::Text, value @ (Self::Int(_) | Self::Float(_) | Self::PrimaryKey(_))) => {
+                Self::Text(value.to_string())
+            }
+            (Affinity::Real, Self::Int(n)) => Self::Float(n as f64),
+            (Affinity::Real, Self::Text(t)) => match parse_number(&t) {
+                Some(Self::Int(n)) => Self::Float(n as f64),
+                Some(n) => n,
+                None => Self::Text(t),
+            },
+            (Affinity::Numeric | Affinity::Integer, Self::Text(t)) => match parse_number(&t) {
+                Some(n) => n.apply_affinity(affinity),
+                None => Self::Text(t),
+            },
+            (Affinity::Numeric | Affinity::Integer, Self::Float(n))
+                if n.fract() == 0.0 && n.abs() < 9.2e18 =>
+            {
+                Self::Int(n as i64)
+            }
+            (_, value) => value,
+        }
+    }
+
+    /// Storage class in SQLite's sort order.
+    fn class(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::PrimaryKey(_) | Self::Int(_) | Self::Float(_) => 1,
+            Self::Text(_) => 2,
+            Self::Blob(_) => 3,
+        }
+    }
+}
+
+/// Text in the form of an integer or real literal, surrounding spaces aside.
+fn parse_number(text: &str) -> Option<RecordValue> {
+    let text = text.trim();
+    let is_literal = text
+        .trim_start_matches(['+', '-'])
+        .starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'));
+    if !is_literal {
+        return None;
+    }
+
+    match text.parse::<i64>() {
+        Ok(n) => Some(RecordValue::Int(n)),
+        Err(_) => text.parse::<f64>().ok().map(RecordValue::Float),
+    }
+}
+
+/// Values are ordered like SQLite sorts them: NULL first, then numbers by
+/// value, then text and blobs by their bytes.
+impl Ord for RecordValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        use RecordValue::*;
+
+        match (self, other) {
+            (Text(a), Text(b)) => a.cmp(b),
+            (Blob(a), Blob(b)) => a.cmp(b),
+            (Int(a), Int(b)) => a.cmp(b),
+            (PrimaryKey(a), PrimaryKey(b)) => a.cmp(b),
+            (PrimaryKey(a), Int(b)) => (*a as i128).cmp(&(*b as i128)),
+            (Int(a), PrimaryKey(b)) => (*a as i128).cmp(&(*b as i128)),
+            (Float(a), Float(b)) => a.total_cmp(b),
+            (Int(a), Float(b)) => int_float_cmp(*a as i128, *b),
+            (PrimaryKey(a), Float(b)) => int_float_cmp(*a as i128, *b),
+            (Float(a), Int(b)) => int_float_cmp(*b as i128, *a).reverse(),
+            (Float(a), PrimaryKey(b)) => int_float_cmp(*b as i128, *a).reverse(),
+            _ => self.class().cmp(&other.class()),
+        }
+    }
+}
+
+impl PartialOrd for RecordValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RecordValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for RecordValue {}
+
+/// Compares an integer with a float exactly, even where the integer has no
+/// exact float representation.
+fn int_float_cmp(i: i128, f: f64) -> Ordering {
+    if f.is_nan() {
+        return Ordering::Greater;
+    }
+    if f >= 2f64.powi(64) {
+        return Ordering::Less;
+    }
+    if f < -(2f64.powi(63)) {
+        return Ordering::Greater;
+    }
+
+    let whole = f.trunc();
+    i.cmp(&(whole as i128))
+        .then_with(|| 0f64.total_cmp(&(f - whole)))
+}
+
 impl PartialEq<&str> for RecordValue {
     fn eq(&self, other: &&str) -> bool {
         match self {
@@ -265,6 +612,193 @@ impl PartialOrd<&str> for RecordValue {
 mod tests {
     use super::*;
 
+    struct OverflowPages(Vec<u8>);
+
+    impl Pager for OverflowPages {
+        fn usable_size(&self) -> Result<usize> {
+            Ok(512)
+        }
+
+        fn overflow(&self, first: PageNum, size: usize) -> Result<Vec<u8>> {
+            assert_eq!(first, 7);
+            Ok(self.0[..size].to_vec())
+        }
+
+        fn text_encoding(&self) -> Result<TextEncoding> {
+            Ok(TextEncoding::Utf8)
+        }
+    }
+
+    #[test]
+    fn it_calculates_local_payload_size() {
+        assert_eq!(local_payload_size(PageType::LeafTable, 100, 4096), 100);
+        assert_eq!(local_payload_size(PageType::LeafTable, 4061, 4096), 4061);
+        assert_eq!(local_payload_size(PageType::LeafTable, 4062, 4096), 489);
+        assert_eq!(local_payload_size(PageType::LeafTable, 10000, 4096), 1816);
+        assert_eq!(local_payload_size(PageType::LeafIndex, 1002, 4096), 1002);
+        assert_eq!(local_payload_size(PageType::LeafIndex, 1003, 4096), 489);
+        assert_eq!(local_payload_size(PageType::InteriorIndex, 2000, 4096), 489);
+    }
+
+    #[test]
+    fn it_encodes_records() {
+        let values = vec![
+            RecordValue::Null,
+            RecordValue::Int(0),
+            RecordValue::Int(1),
+            RecordValue::Int(-200),
+            RecordValue::Int(100_000),
+            RecordValue::Int(1 << 40),
+            RecordValue::Float(1.5),
+            RecordValue::Text("hi".into()),
+            RecordValue::Blob(vec![1, 2]),
+            RecordValue::Int(-1 << 47),
+            RecordValue::Int(1 << 48),
+        ];
+        let bytes = Record::encode(&values, TextEncoding::Utf8);
+        assert_eq!(&bytes[..12], &[12, 0, 8, 9, 2, 3, 5, 7, 17, 16, 5, 6]);
+
+        let record = Record::new(bytes, TextEncoding::Utf8).unwrap();
+        let decoded: Vec<String> = record.0.iter().map(|v| v.to_string()).collect();
+        let expected: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(decoded, expected);
+
+        let many = vec![RecordValue::Null; 200];
+        let record = Record::new(
+            Record::encode(&many, TextEncoding::Utf8),
+            TextEncoding::Utf8,
+        );
+        assert_eq!(record.unwrap().0.len(), 200);
+    }
+
+    #[test]
+    fn it_decodes_utf16_text() {
+        let values = [RecordValue::Text("héllo".into())];
+
+        let bytes = Record::encode(&values, TextEncoding::Utf16le);
+        assert_eq!(&bytes[..4], &[2, 33, b'h', 0]);
+        let record = Record::new(bytes, TextEncoding::Utf16le).unwrap();
+        assert_eq!(record.column(0).unwrap(), "héllo");
+
+        let bytes = Record::encode(&values, TextEncoding::Utf16be);
+        assert_eq!(&bytes[..4], &[2, 33, 0, b'h']);
+        let record = Record::new(bytes, TextEncoding::Utf16be).unwrap();
+        assert_eq!(record.column(0).unwrap(), "héllo");
+    }
+
+    #[test]
+    fn it_decodes_48_bit_integers() {
+        let mut bytes = vec![3, 5, 5];
+        bytes.extend([0x01, 0x8b, 0xcf, 0xe5, 0x7c, 0x00]);
+        bytes.extend([0xff, 0xff, 0xff, 0xff, 0xff, 0xfe]);
+
+        let record = Record::new(bytes, TextEncoding::Utf8).unwrap();
+        assert_eq!(
+            record.column(0).unwrap(),
+            RecordValue::Int(1_700_000_005_120)
+        );
+        assert_eq!(record.column(1).unwrap(), RecordValue::Int(-2));
+    }
+
+    #[test]
+    fn it_reports_corrupt_records() {
+        for serial_type in [10, 11] {
+            let record = Record::new(vec![2, serial_type, 0], TextEncoding::Utf8);
+            assert!(matches!(record, Err(Error::Corrupt { .. })));
+        }
+
+        // A text value far longer than the record.
+        let record = Record::new(vec![6, 0xff, 0xff, 0xff, 0xff, 0x7f], TextEncoding::Utf8);
+        assert!(matches!(record, Err(Error::Corrupt { .. })));
+    }
+
+    #[test]
+    fn it_reads_payload_from_overflow_pages() {
+        let text = "a".repeat(600);
+        // record header: size 3, serial type for 600 bytes of text (13 + 2 * 600)
+        let mut payload = vec![0x03, 0x89, 0x3d];
+        payload.extend(text.as_bytes());
+
+        let local_size = local_payload_size(PageType::LeafTable, payload.len(), 512);
+        let pager = OverflowPages(payload[local_size..].to_vec());
+
+        // payload size 603, rowid 1, local part and the first overflow page number
+        let mut bytes = vec![0x84, 0x5b, 0x01];
+        bytes.extend(&payload[..local_size]);
+        bytes.extend(7u32.to_be_bytes());
+
+        let cell = Cell::new(PageType::LeafTable, &mut Cursor::new(bytes), &pager).unwrap();
+        assert_eq!(cell.rowid(), Some(1));
+        assert_eq!(cell.column(0).unwrap(), text.as_str());
+    }
+
+    #[test]
+    fn it_displays_floats_like_sqlite() {
+        let display = |n: f64| RecordValue::Float(n).to_string();
+        assert_eq!(display(100.0), "100.0");
+        assert_eq!(display(2.5), "2.5");
+        assert_eq!(display(0.1 + 0.2), "0.3");
+        assert_eq!(display(7.0 / 3.0), "2.33333333333333");
+        assert_eq!(display(-0.5), "-0.5");
+        assert_eq!(display(1e20), "1.0e+20");
+        assert_eq!(display(1.5e-7), "1.5e-07");
+        assert_eq!(display(123456789012345678.0), "1.23456789012346e+17");
+    }
+
+    #[test]
+    fn it_compares_integers_with_floats_exactly() {
+        use RecordValue::*;
+
+        assert_eq!(Int(2), Float(2.0));
+        assert_eq!(PrimaryKey(7), Int(7));
+        assert!(Int(i64::MAX) > Float(9.2e18));
+        assert!(Int(i64::MAX) < Float(9.3e18));
+        assert!(Int(i64::MAX - 1) < Float(i64::MAX as f64));
+        assert!(Float(0.5) > Int(0) && Float(-0.5) < Int(0));
+        assert!(Float(f64::INFINITY) > Int(i64::MAX));
+    }
+
+    #[test]
+    fn it_applies_column_affinity() {
+        use RecordValue::*;
+
+        assert_eq!(Affinity::new("varchar(10)"), Affinity::Text);
+        assert_eq!(Affinity::new("BIGINT"), Affinity::Integer);
+        assert_eq!(Affinity::new("double precision"), Affinity::Real);
+        assert_eq!(Affinity::new(""), Affinity::Blob);
+        assert_eq!(Affinity::new("decimal(10,2)"), Affinity::Numeric);
+
+        let text = |t: &str| Text(t.into());
+        assert!(matches!(
+            text(" 5 ").apply_affinity(Affinity::Integer),
+            Int(5)
+        ));
+        assert!(matches!(
+            text("3.0").apply_affinity(Affinity::Numeric),
+            Int(3)
+        ));
+        assert!(matches!(text("1e2").apply_affinity(Affinity::Real), Float(n) if n == 100.0));
+        assert!(matches!(
+            text("5a").apply_affinity(Affinity::Integer),
+            Text(_)
+        ));
+        assert!(matches!(
+            text("inf").apply_affinity(Affinity::Real),
+            Text(_)
+        ));
+        assert!(matches!(Int(5).apply_affinity(Affinity::Real), Float(n) if n == 5.0));
+        assert_eq!(Float(1.5).apply_affinity(Affinity::Text), "1.5");
+        assert_eq!(Int(5).apply_affinity(Affinity::Blob), Int(5));
+        assert!(matches!(
+            Float(2.0).apply_affinity(Affinity::Integer),
+            Int(2)
+        ));
+        assert!(matches!(
+            Float(2.5).apply_affinity(Affinity::Integer),
+            Float(_)
+        ));
+    }
+
     #[test]
     fn it_can_compare_with_strings() {
         let val = RecordValue::Text("foo".into());
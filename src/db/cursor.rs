@@ -0,0 +1,314 @@
+use super::{
+    cell::{Cell, RowId},
+    Db, Error, Page, PageNum, Result,
+};
+use std::io::{Read, Seek};
+
+/// A position in a table b-tree that walks the leaves from left to right.
+///
+/// The cursor keeps the path from the root page as a stack of pages paired
+/// with an index. For an interior page the index is the child being visited,
+/// where the number of cells means the right most pointer. For a leaf page it
+/// is the next cell to return.
+#[derive(Debug)]
+pub struct BtreeCursor<'a, R: Read + Seek> {
+    db: &'a Db<R>,
+    rootpage: PageNum,
+    stack: Vec<(Page, usize)>,
+}
+
+impl<'a, R: Read + Seek> BtreeCursor<'a, R> {
+    pub fn new(db: &'a Db<R>, rootpage: PageNum) -> Result<Self> {
+        Ok(Self {
+            db,
+            rootpage,
+            stack: vec![(db.page(rootpage)?, 0)],
+        })
+    }
+
+    /// Moves the cursor to the first row whose rowid is greater than or equal
+    /// to the given one.
+    pub fn seek(&mut self, rowid: RowId) -> Result<()> {
+        self.stack.clear();
+        let mut page = self.db.page(self.rootpage)?;
+
+        loop {
+            let idx = lower_bound(&mut page, self.db, rowid)?;
+            let child = if page.is_leaf()? {
+                None
+            } else {
+                Some(child_page(&mut page, idx, self.db)?)
+            };
+            self.stack.push((page, idx));
+
+            match child {
+                Some(num) => page = self.db.page(num)?,
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Moves the cursor past the last row and returns that row, if any.
+    pub fn last(&mut self) -> Result<Option<Cell>> {
+        self.stack.clear();
+        let mut page = self.db.page(self.rootpage)?;
+
+        while !page.is_leaf()? {
+            let child = page.right_most_pointer()?;
+            let num_cells = page.num_cells()?;
+            self.stack.push((page, num_cells));
+            page = self.db.page(child)?;
+        }
+
+        let num_cells = page.num_cells()?;
+        let cell = match num_cells {
+            0 => None,
+            n => Some(page.cell(n - 1, self.db)?),
+        };
+        self.stack.push((page, num_cells));
+        Ok(cell)
+    }
+
+    /// Returns the cell at the current position and advances the cursor.
+    pub fn next_cell(&mut self) -> Result<Option<Cell>> {
+        while let Some((page, idx)) = self.stack.last_mut() {
+            let num_cells = page.num_cells()?;
+
+            if page.is_leaf()? {
+                if *idx < num_cells {
+                    let cell = page.cell(*idx, self.db)?;
+                    *idx += 1;
+                    return Ok(Some(cell));
+                }
+                self.pop();
+            } else if *idx <= num_cells {
+                let child = child_page(page, *idx, self.db)?;
+                self.stack.push((self.db.page(child)?, 0));
+            } else {
+                self.pop();
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Leaves the current page and moves its parent to the next child.
+    fn pop(&mut self) {
+        self.stack.pop();
+        if let Some((_, idx)) = self.stack.last_mut() {
+            *idx += 1;
+        }
+    }
+}
+
+/// A position in an index b-tree that walks the keys in order, including the
+/// ones stored in interior pages.
+///
+/// For an interior page the index counts both its children and its cells: an
+/// even index `2 * i` is the child left of cell `i` still to be visited, and
+/// the odd one after it is cell `i` itself.
+#[derive(Debug)]
+pub struct IndexCursor<'a, R: Read + Seek> {
+    db: &'a Db<R>,
+    rootpage: PageNum,
+    stack: Vec<(Page, usize)>,
+}
+
+impl<'a, R: Read + Seek> IndexCursor<'a, R> {
+    pub fn new(db: &'a Db<R>, rootpage: PageNum) -> Result<Self> {
+        Ok(Self {
+            db,
+            rootpage,
+            stack: vec![(db.page(rootpage)?, 0)],
+        })
+    }
+
+    /// Moves the cursor to the first key for which `is_before` is false. The
+    /// keys it holds for must come before all the others.
+    pub fn seek<F: Fn(&Cell) -> bool>(&mut self, is_before: F) -> Result<()> {
+        self.stack.clear();
+        let mut page = self.db.page(self.rootpage)?;
+
+        loop {
+            let (mut lo, mut hi) = (0, page.num_cells()?);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                if is_before(&page.cell(mid, self.db)?) {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+
+            if page.is_leaf()? {
+                self.stack.push((page, lo));
+                return Ok(());
+            }
+
+            // The key is in the child left of cell `lo`, or is that cell.
+            let child = child_page(&mut page, lo, self.db)?;
+            self.stack.push((page, 2 * lo + 1));
+            page = self.db.page(child)?;
+        }
+    }
+
+    /// Returns the cell at the current position and advances the cursor.
+    pub fn next_cell(&mut self) -> Result<Option<Cell>> {
+        while let Some((page, idx)) = self.stack.last_mut() {
+            let num_cells = page.num_cells()?;
+
+            if page.is_leaf()? {
+                if *idx < num_cells {
+                    let cell = page.cell(*idx, self.db)?;
+                    *idx += 1;
+                    return Ok(Some(cell));
+                }
+                self.stack.pop();
+            } else if *idx % 2 == 0 {
+                let child = child_page(page, *idx / 2, self.db)?;
+                *idx += 1;
+                self.stack.push((self.db.page(child)?, 0));
+            } else if *idx / 2 < num_cells {
+                let cell = page.cell(*idx / 2, self.db)?;
+                *idx += 1;
+                return Ok(Some(cell));
+            } else {
+                self.stack.pop();
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+pub(super) fn child_page<R: Read + Seek>(
+    page: &mut Page,
+    idx: usize,
+    db: &Db<R>,
+) -> Result<PageNum> {
+    if idx < page.num_cells()? {
+        page.cell(idx, db)?.left().ok_or_else(|| {
+            Error::corrupt(format!("cell {idx} in interior page has no left pointer"))
+        })
+    } else {
+        page.right_most_pointer()
+    }
+}
+
+/// Index of the first cell whose rowid is greater than or equal to the given
+/// one, or the number of cells if there is no such cell.
+pub(super) fn lower_bound<R: Read + Seek>(
+    page: &mut Page,
+    db: &Db<R>,
+    rowid: RowId,
+) -> Result<usize> {
+    let (mut lo, mut hi) = (0, page.num_cells()?);
+
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if page.cell(mid, db)?.rowid().is_some_and(|id| id < rowid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Ok(lo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        test_utils::{index_page, sample_db},
+        DbFile, RecordValue,
+    };
+    use std::io::Cursor;
+
+    fn rowids<R: Read + Seek>(cursor: &mut BtreeCursor<'_, R>) -> Vec<RowId> {
+        let mut rowids = vec![];
+        while let Some(cell) = cursor.next_cell().unwrap() {
+            rowids.push(cell.rowid().unwrap());
+        }
+        rowids
+    }
+
+    #[test]
+    fn it_walks_table_leaves_in_rowid_order() {
+        let db = DbFile::from_path("sample.db").unwrap();
+        let mut cursor = BtreeCursor::new(&db, 4).unwrap();
+        assert_eq!(rowids(&mut cursor), vec![1, 2, 3, 4, 5, 6]);
+        assert!(cursor.next_cell().unwrap().is_none());
+    }
+
+    #[test]
+    fn it_seeks_to_rowid() {
+        let db = DbFile::from_path("sample.db").unwrap();
+        let mut cursor = BtreeCursor::new(&db, 4).unwrap();
+
+        cursor.seek(4).unwrap();
+        assert_eq!(rowids(&mut cursor), vec![4, 5, 6]);
+
+        cursor.seek(7).unwrap();
+        assert_eq!(rowids(&mut cursor), Vec::<RowId>::new());
+
+        assert_eq!(cursor.last().unwrap().and_then(|c| c.rowid()), Some(6));
+        assert!(cursor.next_cell().unwrap().is_none());
+    }
+
+    #[test]
+    fn it_reports_the_corrupt_page() {
+        let mut bytes = std::fs::read("sample.db").unwrap();
+        // Page 4 with a cell pointer past the end of the page.
+        bytes[3 * 4096 + 8] = 0xff;
+        bytes[3 * 4096 + 9] = 0xff;
+        let db = Db::new(Cursor::new(bytes));
+
+        let mut cursor = BtreeCursor::new(&db, 4).unwrap();
+        let err = cursor.next_cell().unwrap_err();
+        assert!(matches!(err, Error::Corrupt { page: Some(4), .. }));
+        assert_eq!(err.code(), crate::ResultCode::Corrupt);
+    }
+
+    fn keys<R: Read + Seek>(cursor: &mut IndexCursor<'_, R>) -> Vec<i64> {
+        let mut keys = vec![];
+        while let Some(cell) = cursor.next_cell().unwrap() {
+            match cell.index_payload().unwrap().0 {
+                [RecordValue::Int(key)] => keys.push(*key),
+                key => panic!("Unexpected key {key:?}"),
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn it_walks_index_keys_in_order() {
+        // Page 5 is the root with keys 30 and 60 between the leaves 6, 7 and 8.
+        let entry = |left, key, rowid| (left, vec![RecordValue::Int(key), RecordValue::Int(rowid)]);
+        let db = sample_db(&[
+            index_page(&[entry(Some(6), 30, 3), entry(Some(7), 60, 6)], Some(8)),
+            index_page(&[entry(None, 10, 1), entry(None, 20, 2)], None),
+            index_page(&[entry(None, 40, 4), entry(None, 50, 5)], None),
+            index_page(&[entry(None, 70, 7)], None),
+        ]);
+
+        let mut cursor = IndexCursor::new(&db, 5).unwrap();
+        assert_eq!(keys(&mut cursor), vec![10, 20, 30, 40, 50, 60, 70]);
+
+        let seek = |cursor: &mut IndexCursor<'_, _>, key: i64| {
+            cursor
+                .seek(|cell| matches!(cell.index_payload(), Some(([RecordValue::Int(k)], _)) if *k < key))
+                .unwrap()
+        };
+        seek(&mut cursor, 25);
+        assert_eq!(keys(&mut cursor), vec![30, 40, 50, 60, 70]);
+        seek(&mut cursor, 40);
+        assert_eq!(keys(&mut cursor), vec![40, 50, 60, 70]);
+        seek(&mut cursor, 65);
+        assert_eq!(keys(&mut cursor), vec![70]);
+        seek(&mut cursor, 0);
+        assert_eq!(keys(&mut cursor), vec![10, 20, 30, 40, 50, 60, 70]);
+        seek(&mut cursor, 71);
+        assert!(keys(&mut cursor).is_empty());
+    }
+}
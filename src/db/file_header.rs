@@ -1,6 +1,71 @@
+use super::{Error, Result};
+use std::fmt;
+
 pub(super) const FILE_HEADER_SIZE: usize = 100;
 
-#[derive(Debug)]
+/// The encoding of all text in the database.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf8,
+    Utf16le,
+    Utf16be,
+}
+
+impl TextEncoding {
+    fn new(value: u32) -> Result<Self> {
+        match value {
+            1 => Ok(Self::Utf8),
+            2 => Ok(Self::Utf16le),
+            3 => Ok(Self::Utf16be),
+            _ => Err(Error::corrupt(format!("invalid text encoding: {value}")).on_page(1)),
+        }
+    }
+
+    pub fn decode(&self, bytes: Vec<u8>) -> Result<String> {
+        let units = |to_u16: fn([u8; 2]) -> u16| {
+            bytes
+                .chunks_exact(2)
+                .map(|pair| to_u16([pair[0], pair[1]]))
+                .collect::<Vec<u16>>()
+        };
+
+        match self {
+            Self::Utf8 => Ok(String::from_utf8(bytes)?),
+            Self::Utf16le => Ok(String::from_utf16(&units(u16::from_le_bytes))?),
+            Self::Utf16be => Ok(String::from_utf16(&units(u16::from_be_bytes))?),
+        }
+    }
+
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Self::Utf8 => text.as_bytes().to_vec(),
+            Self::Utf16le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            Self::Utf16be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        }
+    }
+
+    /// The value stored in the file header.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Utf8 => 1,
+            Self::Utf16le => 2,
+            Self::Utf16be => 3,
+        }
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utf8 => write!(f, "utf8"),
+            Self::Utf16le => write!(f, "utf16le"),
+            Self::Utf16be => write!(f, "utf16be"),
+        }
+    }
+}
+
+/// The first 100 bytes of the database file.
+#[derive(Debug, Clone)]
 pub struct FileHeader([u8; FILE_HEADER_SIZE]);
 
 impl FileHeader {
@@ -8,7 +73,151 @@ impl FileHeader {
         Self(buf)
     }
 
-    pub fn page_size(&self) -> u16 {
-        u16::from_be_bytes([self.0[16], self.0[17]])
+    /// Page size in bytes, where the value 1 stands for 65536 which does not
+    /// fit in the two bytes of the field.
+    pub fn page_size(&self) -> u32 {
+        match u16::from_be_bytes([self.0[16], self.0[17]]) {
+            1 => 65536,
+            size => size as u32,
+        }
+    }
+
+    /// File format write version, 1 for legacy and 2 for WAL.
+    pub fn write_version(&self) -> u8 {
+        self.0[18]
+    }
+
+    /// File format read version, 1 for legacy and 2 for WAL.
+    pub fn read_version(&self) -> u8 {
+        self.0[19]
+    }
+
+    /// Bytes reserved at the end of each page.
+    pub fn reserved_space(&self) -> u8 {
+        self.0[20]
+    }
+
+    pub fn max_embedded_payload_fraction(&self) -> u8 {
+        self.0[21]
+    }
+
+    pub fn min_embedded_payload_fraction(&self) -> u8 {
+        self.0[22]
+    }
+
+    pub fn leaf_payload_fraction(&self) -> u8 {
+        self.0[23]
+    }
+
+    pub fn change_counter(&self) -> u32 {
+        self.read_u32(24)
+    }
+
+    /// Size of the database in pages, which is only valid when the version
+    /// valid for number matches the change counter.
+    pub fn page_count(&self) -> u32 {
+        self.read_u32(28)
+    }
+
+    pub fn freelist_trunk_page(&self) -> u32 {
+        self.read_u32(32)
+    }
+
+    pub fn freelist_page_count(&self) -> u32 {
+        self.read_u32(36)
+    }
+
+    pub fn schema_cookie(&self) -> u32 {
+        self.read_u32(40)
+    }
+
+    pub fn schema_format(&self) -> u32 {
+        self.read_u32(44)
+    }
+
+    pub fn default_cache_size(&self) -> u32 {
+        self.read_u32(48)
+    }
+
+    /// Page number of the largest root b-tree page in auto-vacuum or
+    /// incremental-vacuum modes, or 0 otherwise.
+    pub fn auto_vacuum_root(&self) -> u32 {
+        self.read_u32(52)
+    }
+
+    pub fn text_encoding(&self) -> Result<TextEncoding> {
+        TextEncoding::new(self.read_u32(56))
+    }
+
+    pub fn user_version(&self) -> u32 {
+        self.read_u32(60)
+    }
+
+    /// Non-zero for incremental-vacuum mode.
+    pub fn incremental_vacuum(&self) -> u32 {
+        self.read_u32(64)
+    }
+
+    pub fn application_id(&self) -> u32 {
+        self.read_u32(68)
+    }
+
+    pub fn version_valid_for(&self) -> u32 {
+        self.read_u32(92)
+    }
+
+    /// `SQLITE_VERSION_NUMBER` of the library which last wrote the file.
+    pub fn sqlite_version_number(&self) -> u32 {
+        self.read_u32(96)
+    }
+
+    pub(super) fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Sets the database size and marks it valid for the current change
+    /// counter.
+    pub(super) fn set_page_count(&mut self, count: u32) {
+        self.write_u32(28, count);
+        self.write_u32(92, self.change_counter());
+    }
+
+    /// Counts a change to the database, keeping the page count valid.
+    pub(super) fn increment_change_counter(&mut self) {
+        let counter = self.change_counter().wrapping_add(1);
+        self.write_u32(24, counter);
+        self.write_u32(92, counter);
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        u32::from_be_bytes([
+            self.0[offset],
+            self.0[offset + 1],
+            self.0[offset + 2],
+            self.0[offset + 3],
+        ])
+    }
+
+    fn write_u32(&mut self, offset: usize, value: u32) {
+        self.0[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_largest_page_size() {
+        let mut buf = [0u8; FILE_HEADER_SIZE];
+        buf[16..18].copy_from_slice(&1u16.to_be_bytes());
+        buf[56..60].copy_from_slice(&3u32.to_be_bytes());
+        let header = FileHeader::new(buf);
+
+        assert_eq!(header.page_size(), 65536);
+        assert_eq!(header.text_encoding().unwrap(), TextEncoding::Utf16be);
+
+        buf[16..18].copy_from_slice(&512u16.to_be_bytes());
+        assert_eq!(FileHeader::new(buf).page_size(), 512);
     }
 }
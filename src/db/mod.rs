@@ -1,57 +1,154 @@
+mod btree;
 mod cell;
+mod cursor;
 pub mod file_header;
 mod page;
 mod schema_table;
 mod table;
+#[cfg(test)]
+mod test_utils;
 mod varint;
+mod wal;
 
 use super::{err, sql, utils, Error, Result};
-use file_header::{FileHeader, FILE_HEADER_SIZE};
+use cell::Pager;
+pub use cell::{Affinity, RecordValue};
+use file_header::{FileHeader, TextEncoding, FILE_HEADER_SIZE};
 use page::Page;
 use schema_table::Schema;
+use sql::{create::ViewDefinition, parsers::parse_create_view};
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{Read, Seek, SeekFrom},
-    path::Path,
+    collections::{BTreeSet, HashMap},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
     rc::Rc,
     sync::{Mutex, MutexGuard},
 };
 pub use table::{Table, TableRow, TableRows};
+use wal::Wal;
 
-pub type DbFile = Db<File>;
+pub type DbFile = Db<FileHandle>;
 
-impl Db<File> {
+impl DbFile {
+    /// Opens the database for reading. The file is only reopened for writing
+    /// once a statement writes to it. A `-wal` file next to it is read as its
+    /// write-ahead log.
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        File::open(path).map(Self::new).map_err(Error::from)
+        let db = FileHandle::open(&path).map(Self::new)?;
+
+        let mut wal_path = PathBuf::from(path.as_ref()).into_os_string();
+        wal_path.push("-wal");
+        match FileHandle::open(wal_path) {
+            Ok(file) => db.with_wal(file),
+            Err(_) => Ok(db),
+        }
+    }
+}
+
+/// A file opened for reading that is reopened for reading and writing the
+/// first time it is written to.
+#[derive(Debug)]
+pub struct FileHandle {
+    path: PathBuf,
+    file: File,
+    writable: bool,
+}
+
+impl FileHandle {
+    fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            path: path.as_ref().into(),
+            file: File::open(&path)?,
+            writable: false,
+        })
+    }
+}
+
+impl Read for FileHandle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for FileHandle {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
     }
 }
 
-type PageNum = u32;
+impl Write for FileHandle {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.writable {
+            let position = self.file.stream_position()?;
+            self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+            self.file.seek(SeekFrom::Start(position))?;
+            self.writable = true;
+        }
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+pub type PageNum = u32;
 type Pages = HashMap<PageNum, PageBuffer>;
 
 #[derive(Debug)]
 pub struct Db<R: Read + Seek> {
     r: Mutex<R>,
+    wal: Option<Wal<R>>,
+    header: Mutex<Option<FileHeader>>,
     pages: Mutex<Pages>,
+    /// Pages written since the last commit, which are only kept in `pages`
+    /// until the commit writes them to the file.
+    dirty: Mutex<BTreeSet<PageNum>>,
 }
 
 impl<R: Read + Seek> Db<R> {
     pub fn new(r: R) -> Self {
         Self {
             r: Mutex::new(r),
+            wal: None,
+            header: Mutex::new(None),
             pages: Mutex::new(HashMap::new()),
+            dirty: Mutex::new(BTreeSet::new()),
         }
     }
 
+    /// Reads pages from the committed frames of the write-ahead log before
+    /// falling back to the database file. An empty log is ignored.
+    pub fn with_wal(self, r: R) -> Result<Self> {
+        let wal = Wal::new(r)?;
+        Ok(Self {
+            wal: (!wal.is_empty()).then_some(wal),
+            header: Mutex::new(None),
+            pages: Mutex::new(HashMap::new()),
+            ..self
+        })
+    }
+
     pub fn file_header(&self) -> Result<FileHeader> {
+        let mut header = self.lock_header()?;
+        if let Some(header) = header.as_ref() {
+            return Ok(header.clone());
+        }
+
         let mut buf = [0u8; FILE_HEADER_SIZE];
-        self.read_db(0, &mut buf)?;
-        Ok(FileHeader::new(buf))
+        self.read_page(1, &mut buf)?;
+        Ok(header.insert(FileHeader::new(buf)).clone())
+    }
+
+    /// Number of schema objects of the type, such as `table` or `index`.
+    pub fn num_objects(&self, r#type: &str) -> Result<usize> {
+        Ok(self.schemas()?.filter(|row| row.r#type() == r#type).count())
     }
 
-    pub fn num_tables(&self) -> Result<usize> {
-        self.schema_page()?.num_cells()
+    /// Total length in characters of the SQL text of all schema objects.
+    pub fn schema_size(&self) -> Result<usize> {
+        Ok(self.schemas()?.map(|row| row.sql().chars().count()).sum())
     }
 
     pub fn table_names(&self) -> Result<Vec<String>> {
@@ -67,17 +164,43 @@ impl<R: Read + Seek> Db<R> {
         Table::builder(name).db(self).schemas(schemas).build()
     }
 
+    /// The definition of the view with the name, if there is one.
+    pub fn view(&self, name: &str) -> Result<Option<ViewDefinition>> {
+        let Some(schema) = self
+            .schemas()?
+            .find(|row| row.r#type() == "view" && row.tbl_name().eq_ignore_ascii_case(name))
+        else {
+            return Ok(None);
+        };
+        let sql = schema.sql();
+        let (_, view) = parse_create_view(sql).map_err(|_| table::unsupported_schema(sql))?;
+        Ok(Some(view))
+    }
+
+    /// The rows of the schema table, whose b-tree is rooted at the first page.
     fn schemas(&self) -> Result<impl Iterator<Item = Schema>> {
-        Ok(self
-            .schema_page()?
-            .cells()?
-            .into_iter()
-            .filter_map(|cell| Schema::new(cell).ok()))
+        let mut cursor = cursor::BtreeCursor::new(self, 1)?;
+        let mut schemas = vec![];
+        while let Some(cell) = cursor.next_cell()? {
+            schemas.push(Schema::new(cell)?);
+        }
+        Ok(schemas.into_iter())
     }
 
     fn page(&self, num: PageNum) -> Result<Page> {
+        let buf = self.page_buffer(num)?;
+        let header_offset = if num == 1 { FILE_HEADER_SIZE } else { 0 };
+
+        Ok(Page::builder()
+            .num(num)
+            .header_offset(header_offset as u64)
+            .buffer(buf)
+            .build())
+    }
+
+    fn page_buffer(&self, num: PageNum) -> Result<PageBuffer> {
         if num == 0 {
-            return Err(err!("page number must be greater than 0"));
+            return Err(Error::corrupt("page number must be greater than 0"));
         }
 
         let mut pages = self.lock_pages()?;
@@ -86,8 +209,7 @@ impl<R: Read + Seek> Db<R> {
             None => {
                 let page_size = self.file_header()?.page_size() as usize;
                 let mut buf = vec![0u8; page_size];
-                let offset = (num - 1) as u64 * page_size as u64;
-                self.read_db(offset, &mut buf)?;
+                self.read_page(num, &mut buf)?;
 
                 let buf = PageBuffer::from(buf);
                 pages.insert(num, buf.clone());
@@ -95,16 +217,23 @@ impl<R: Read + Seek> Db<R> {
             }
         };
 
-        let header_offset = if num == 1 { FILE_HEADER_SIZE } else { 0 };
-
-        Ok(Page::builder()
-            .header_offset(header_offset as u64)
-            .buffer(buf)
-            .build())
+        Ok(buf)
     }
 
-    fn schema_page(&self) -> Result<Page> {
-        self.page(1)
+    /// Reads the start of a page into the buffer, from the write-ahead log if
+    /// it has the page.
+    fn read_page(&self, num: PageNum, buf: &mut [u8]) -> Result<()> {
+        if let Some(wal) = &self.wal {
+            if wal.read_page(num, buf)? {
+                return Ok(());
+            }
+        }
+
+        let offset = match num {
+            1 => 0,
+            _ => (num - 1) as u64 * self.file_header()?.page_size() as u64,
+        };
+        self.read_db(offset, buf)
     }
 
     fn read_db(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
@@ -114,13 +243,138 @@ impl<R: Read + Seek> Db<R> {
         Ok(())
     }
 
-    fn lock_db(&self) -> Result<MutexGuard<R>> {
+    fn lock_db(&self) -> Result<MutexGuard<'_, R>> {
         self.r.lock().map_err(Error::from)
     }
 
-    fn lock_pages(&self) -> Result<MutexGuard<Pages>> {
+    fn lock_pages(&self) -> Result<MutexGuard<'_, Pages>> {
         self.pages.lock().map_err(Error::from)
     }
+
+    fn lock_dirty(&self) -> Result<MutexGuard<'_, BTreeSet<PageNum>>> {
+        self.dirty.lock().map_err(Error::from)
+    }
+
+    fn lock_header(&self) -> Result<MutexGuard<'_, Option<FileHeader>>> {
+        self.header.lock().map_err(Error::from)
+    }
+
+    /// Size of the database in pages, taken from the file size when the header
+    /// was last written by a version of SQLite which did not maintain it.
+    fn page_count(&self) -> Result<u32> {
+        if let Some(size) = self.wal.as_ref().and_then(|wal| wal.db_size()) {
+            return Ok(size);
+        }
+
+        let header = self.file_header()?;
+        if header.page_count() > 0 && header.version_valid_for() == header.change_counter() {
+            return Ok(header.page_count());
+        }
+
+        let len = self.lock_db()?.seek(SeekFrom::End(0))?;
+        Ok((len / header.page_size() as u64) as u32)
+    }
+}
+
+impl<R: Read + Write + Seek> Db<R> {
+    /// Writes the pages changed since the last commit to the file, with the
+    /// file header's change counter incremented, which tells other readers
+    /// that the database has changed.
+    pub fn commit(&self) -> Result<()> {
+        let mut header = self.file_header()?;
+        header.increment_change_counter();
+
+        let mut page = self.page_buffer(1)?.as_ref().to_vec();
+        page[..FILE_HEADER_SIZE].copy_from_slice(header.as_bytes());
+        self.write_page(1, page)?;
+        *self.lock_header()? = Some(header);
+
+        let mut dirty = self.lock_dirty()?;
+        let pages = self.lock_pages()?;
+        let mut w = self.lock_db()?;
+        for num in dirty.iter() {
+            let buf = pages
+                .get(num)
+                .ok_or_else(|| err!("page {num} was changed but is not cached"))?;
+            let buf = buf.as_ref();
+            w.seek(SeekFrom::Start((num - 1) as u64 * buf.len() as u64))?;
+            w.write_all(buf)?;
+        }
+        w.flush()?;
+        dirty.clear();
+        Ok(())
+    }
+
+    /// Forgets the pages changed since the last commit, leaving the file as
+    /// it was.
+    pub fn rollback(&self) -> Result<()> {
+        let mut dirty = self.lock_dirty()?;
+        let mut pages = self.lock_pages()?;
+        for num in dirty.iter() {
+            pages.remove(num);
+        }
+        dirty.clear();
+        *self.lock_header()? = None;
+        Ok(())
+    }
+
+    /// Adds a page at the end of the database.
+    fn allocate_page(&self) -> Result<PageNum> {
+        let num = self.page_count()? + 1;
+        let mut header = self.file_header()?;
+        header.set_page_count(num);
+        *self.lock_header()? = Some(header);
+        Ok(num)
+    }
+
+    fn write_page(&self, num: PageNum, buf: Vec<u8>) -> Result<()> {
+        if num == 0 {
+            return Err(Error::corrupt("page number must be greater than 0"));
+        }
+        if self.wal.is_some() {
+            return Err(err!(
+                "Cannot write to a database with frames in its write-ahead log"
+            ));
+        }
+
+        self.lock_pages()?.insert(num, PageBuffer::from(buf));
+        self.lock_dirty()?.insert(num);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Pager for Db<R> {
+    fn usable_size(&self) -> Result<usize> {
+        let header = self.file_header()?;
+        Ok(header.page_size() as usize - header.reserved_space() as usize)
+    }
+
+    fn overflow(&self, first: PageNum, size: usize) -> Result<Vec<u8>> {
+        let usable_size = self.usable_size()?;
+        let mut bytes: Vec<u8> = Vec::with_capacity(size);
+        let mut next = first;
+
+        while bytes.len() < size {
+            if next == 0 {
+                return Err(Error::corrupt(format!(
+                    "overflow chain ended after {} of {size} bytes",
+                    bytes.len()
+                )));
+            }
+
+            let buf = self.page_buffer(next)?;
+            let page = buf.as_ref();
+            let len = (size - bytes.len()).min(usable_size - 4);
+            bytes.extend_from_slice(&page[4..4 + len]);
+            next = u32::from_be_bytes([page[0], page[1], page[2], page[3]]);
+        }
+
+        Ok(bytes)
+    }
+
+    fn text_encoding(&self) -> Result<TextEncoding> {
+        self.file_header()?.text_encoding()
+    }
 }
 
 #[derive(Debug)]
@@ -143,3 +397,110 @@ impl Clone for PageBuffer {
         Self(Rc::clone(&self.0))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sql;
+    use cell::Record;
+    use std::io::Cursor;
+
+    /// The sample database with a row for each view in its schema table.
+    fn db_with_views(views: &[(&str, &str)]) -> Db<Cursor<Vec<u8>>> {
+        let db = test_utils::sample_db(&[]);
+        for (rowid, (name, sql)) in (4..).zip(views) {
+            let values = [
+                RecordValue::Text("view".into()),
+                RecordValue::Text(name.to_string()),
+                RecordValue::Text(name.to_string()),
+                RecordValue::Int(0),
+                RecordValue::Text(sql.to_string()),
+            ];
+            let record = Record::encode(&values, TextEncoding::Utf8);
+            db.insert_record(1, rowid, &record).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn it_reads_views() -> Result<()> {
+        let db = db_with_views(&[
+            (
+                "red",
+                "CREATE VIEW red AS SELECT id, name FROM apples WHERE color LIKE '%red%'",
+            ),
+            (
+                "pairs",
+                "CREATE VIEW pairs(apple, orange) AS \
+                 SELECT red.name, o.name FROM red JOIN oranges o USING (id)",
+            ),
+            ("bad", "CREATE VIEW bad(a, b) AS SELECT id FROM apples"),
+            ("v1", "CREATE VIEW v1 AS SELECT * FROM v2"),
+            (
+                "v2",
+                "CREATE VIEW v2 AS SELECT * FROM red JOIN v1 USING (id)",
+            ),
+            ("v3", "CREATE VIEW v3 AS SELECT * FROM (SELECT * FROM v3)"),
+        ]);
+        let query = |sql| Sql::new(sql)?.execute(&db);
+
+        assert_eq!(
+            query("select name from Red order by id desc")?,
+            vec!["Honeycrisp", "Fuji"]
+        );
+        assert_eq!(
+            query("select p.orange from pairs p where p.apple = 'Fuji'")?,
+            vec!["Tangelo"]
+        );
+        assert_eq!(
+            query("select count(*) from oranges where id in (select id from red)")?,
+            vec!["2"]
+        );
+        assert!(query("select * from bad")
+            .unwrap_err()
+            .to_string()
+            .contains("expected 2 columns for 'bad' but got 1"));
+        // Views reading from themselves, directly or not, would never end.
+        for (view, sql) in [("v1", "select * from v1"), ("v2", "select * from v2")] {
+            assert!(query(sql)
+                .unwrap_err()
+                .to_string()
+                .contains(&format!("view {view} is circularly defined")));
+        }
+        assert!(
+            query("select * from apples where id in (select id from v3)")
+                .unwrap_err()
+                .to_string()
+                .contains("view v3 is circularly defined")
+        );
+        // A view read twice by the same query is not a cycle.
+        assert_eq!(
+            query("select count(*) from red a join red b using (id)")?,
+            vec!["2"]
+        );
+        assert_eq!(db.num_objects("view")?, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn it_opens_files_for_writing_only_when_written() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("file-handle-{}.db", std::process::id()));
+        std::fs::write(&path, b"abcdef")?;
+        let mut file = FileHandle::open(&path)?;
+        let mut buf = [0; 2];
+        file.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"ab");
+        assert!(!file.writable);
+
+        file.write_all(b"XY")?;
+        assert!(file.writable);
+        file.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"ef");
+        drop(file);
+
+        let bytes = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(bytes, b"abXYef");
+        Ok(())
+    }
+}
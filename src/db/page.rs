@@ -1,16 +1,21 @@
 use super::{
-    cell::{Cell, RowId},
-    err, utils, PageBuffer, PageNum, Result,
+    cell::{self, Cell, Pager},
+    utils, Error, PageBuffer, PageNum, Result,
 };
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
 
 #[derive(Debug, Default)]
 pub struct PageBuilder {
+    num: PageNum,
     header_offset: u64,
     buf: Option<PageBuffer>,
 }
 
 impl PageBuilder {
+    pub fn num(self, num: PageNum) -> Self {
+        Self { num, ..self }
+    }
+
     pub fn header_offset(self, offset: u64) -> Self {
         Self {
             header_offset: offset,
@@ -26,8 +31,13 @@ impl PageBuilder {
     }
 
     pub fn build(self) -> Page {
-        let Self { header_offset, buf } = self;
+        let Self {
+            num,
+            header_offset,
+            buf,
+        } = self;
         Page {
+            num,
             header_offset,
             cursor: Cursor::new(
                 buf.expect("You must set buffer to PageBuilder before building Page"),
@@ -51,15 +61,26 @@ impl PageType {
             0x05 => Ok(Self::InteriorTable),
             0x0a => Ok(Self::LeafIndex),
             0x0d => Ok(Self::LeafTable),
-            _ => Err(err!("Invalid byte for page type: {byte:#04x}")),
+            _ => Err(Error::corrupt(format!(
+                "invalid byte for page type: {byte:#04x}"
+            ))),
         }
     }
 
-    fn is_leaf(&self) -> bool {
+    pub fn byte(&self) -> u8 {
+        match self {
+            Self::InteriorIndex => 0x02,
+            Self::InteriorTable => 0x05,
+            Self::LeafIndex => 0x0a,
+            Self::LeafTable => 0x0d,
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
         matches!(self, Self::LeafIndex | Self::LeafTable)
     }
 
-    fn header_size(&self) -> usize {
+    pub fn header_size(&self) -> usize {
         if self.is_leaf() {
             8
         } else {
@@ -107,6 +128,7 @@ impl Header {
 
 #[derive(Debug, Clone)]
 pub struct Page {
+    num: PageNum,
     header_offset: u64,
     cursor: Cursor<PageBuffer>,
 }
@@ -120,92 +142,79 @@ impl Page {
         self.header().map(|h| h.num_of_cells as usize)
     }
 
-    pub fn cells(&mut self) -> Result<Vec<Cell>> {
-        let r#type = self.r#type()?;
-        let mut cells: Vec<Cell> = vec![];
+    pub fn cell<P: Pager>(&mut self, idx: usize, pager: &P) -> Result<Cell> {
+        self.read_cell(idx, pager).map_err(|e| self.error(e))
+    }
 
-        for p in self.cell_pointers()? {
-            self.set_offset(p)?;
-            cells.push(Cell::new(r#type, &mut self.cursor)?);
-        }
+    /// The bytes of a cell as stored on the page, without decoding it.
+    pub fn raw_cell(&mut self, idx: usize, usable_size: usize) -> Result<Vec<u8>> {
+        self.read_raw_cell(idx, usable_size)
+            .map_err(|e| self.error(e))
+    }
 
-        Ok(cells)
+    pub fn is_leaf(&mut self) -> Result<bool> {
+        self.r#type().map(|t| t.is_leaf())
     }
 
-    pub fn btree_scan(&mut self, key: RowId) -> Result<BtreeSearch> {
-        match self.r#type()? {
-            PageType::InteriorTable => {
-                let page_num = match self.cells()?.into_iter().find_map(next_page(key)) {
-                    Some(left) => left,
-                    None => self
-                        .header()?
-                        .right_most_pointer
-                        .ok_or(err!("Not set right most pointer in interior table page"))?,
-                };
-                Ok(BtreeSearch::Pointer(page_num))
-            }
-            PageType::LeafTable => {
-                let cell = self.cells()?.into_iter().find(next_cell(key));
-                Ok(BtreeSearch::Leaf(cell))
-            }
-            _ => Err(err!("Cannot get index b-tree node from rowid")),
-        }
+    pub fn page_type(&mut self) -> Result<PageType> {
+        self.r#type()
     }
 
-    pub fn btree_search(&mut self, last_rowid: RowId, key: &str) -> Result<BtreeIndexSearch> {
-        match self.r#type()? {
-            PageType::InteriorIndex => {
-                let search = match self
-                    .cells()?
-                    .into_iter()
-                    .find_map(next_index_page(last_rowid, key))
-                {
-                    Some((left, rowid)) => BtreeIndexSearch::PointerOrRowId(left, rowid),
-                    None => {
-                        let page_num = self
-                            .header()?
-                            .right_most_pointer
-                            .ok_or(err!("Not set right most pointer in interior index page"))?;
-                        BtreeIndexSearch::PointerOrRowId(page_num, None)
-                    }
-                };
-                Ok(search)
-            }
-            PageType::LeafIndex => {
-                let rowid = self
-                    .cells()?
-                    .into_iter()
-                    .find_map(next_rowid(last_rowid, key));
-                Ok(BtreeIndexSearch::RowId(rowid))
-            }
-            _ => Err(err!("Cannot use table b-tree node for index search")),
-        }
+    pub fn right_most_pointer(&mut self) -> Result<PageNum> {
+        self.header()?.right_most_pointer.ok_or_else(|| {
+            self.error(Error::corrupt(
+                "expected an interior page with a right-most pointer",
+            ))
+        })
     }
 
     fn header(&mut self) -> Result<Header> {
         self.set_offset(self.header_offset)?;
-        Header::new(&mut self.cursor)
+        Header::new(&mut self.cursor).map_err(|e| self.error(e))
     }
 
-    fn r#type(&mut self) -> Result<PageType> {
-        self.header().map(|h| h.r#type)
+    fn read_cell<P: Pager>(&mut self, idx: usize, pager: &P) -> Result<Cell> {
+        let r#type = self.r#type()?;
+        self.set_offset_from_header(2 * idx as u64)?;
+        let pointer = u16::from_be_bytes(utils::read_2_bytes(&mut self.cursor)?);
+        self.set_offset(pointer as u64)?;
+        Cell::new(r#type, &mut self.cursor, pager)
     }
 
-    fn cell_pointers(&mut self) -> Result<Vec<u64>> {
-        let num_cells = self.num_cells()?;
-        self.set_offset_from_header(0)?;
+    fn read_raw_cell(&mut self, idx: usize, usable_size: usize) -> Result<Vec<u8>> {
+        let r#type = self.r#type()?;
+        self.set_offset_from_header(2 * idx as u64)?;
+        let pointer = u16::from_be_bytes(utils::read_2_bytes(&mut self.cursor)?) as usize;
+
+        let page = self.cursor.get_ref().as_ref();
+        let end = page.len().min(usable_size);
+        if pointer >= end {
+            return Err(Error::corrupt(format!(
+                "cell pointer {pointer} is out of the page"
+            )));
+        }
+        let size = cell::cell_size(r#type, &page[pointer..end], usable_size)?;
+        page.get(pointer..pointer + size)
+            .map(|bytes| bytes.to_vec())
+            .ok_or_else(|| {
+                Error::corrupt(format!("cell at {pointer} runs past the end of the page"))
+            })
+    }
+
+    /// Attributes an error to the page, where reading past its end means that
+    /// it is corrupt.
+    fn error(&self, err: Error) -> Error {
+        match err {
+            Error::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Error::corrupt("content runs past the end of the page")
+            }
+            err => err,
+        }
+        .on_page(self.num)
+    }
 
-        utils::read_n_bytes(&mut self.cursor, 2 * num_cells).map(|bytes| {
-            bytes
-                .chunks(2)
-                .map(|chunk| {
-                    let buf: [u8; 2] = chunk
-                        .try_into()
-                        .expect("Cannot turn 2 bytes slice into an array");
-                    u16::from_be_bytes(buf) as u64
-                })
-                .collect()
-        })
+    fn r#type(&mut self) -> Result<PageType> {
+        self.header().map(|h| h.r#type)
     }
 
     fn set_offset_from_header(&mut self, offset: u64) -> Result<()> {
@@ -218,63 +227,3 @@ impl Page {
         Ok(())
     }
 }
-
-#[derive(Debug)]
-pub enum BtreeSearch {
-    Pointer(PageNum),
-    Leaf(Option<Cell>),
-}
-
-fn next_page(key: RowId) -> Box<dyn FnMut(Cell) -> Option<PageNum>> {
-    Box::new(move |cell: Cell| {
-        if let Cell::InteriorTable { left, rowid } = cell {
-            if rowid >= key {
-                return Some(left);
-            }
-        }
-        None
-    })
-}
-
-fn next_cell(key: RowId) -> Box<dyn FnMut(&Cell) -> bool> {
-    Box::new(move |cell: &Cell| cell.rowid().is_some_and(|rowid| rowid >= key))
-}
-
-#[derive(Debug)]
-pub enum BtreeIndexSearch {
-    PointerOrRowId(PageNum, Option<RowId>),
-    RowId(Option<RowId>),
-}
-
-type NextIndex = Box<dyn FnMut(Cell) -> Option<(PageNum, Option<RowId>)>>;
-
-fn next_index_page(last_rowid: RowId, key: &str) -> NextIndex {
-    let key = key.to_owned();
-
-    Box::new(move |cell: Cell| {
-        let key = key.as_str();
-
-        if let Some((v, rowid)) = cell.index_payload() {
-            if v > key {
-                return cell.left().map(|n| (n, None));
-            }
-            if rowid > last_rowid && v == key {
-                return cell.left().map(|n| (n, Some(rowid)));
-            }
-        }
-        None
-    })
-}
-
-fn next_rowid(last_rowid: RowId, key: &str) -> Box<dyn FnMut(Cell) -> Option<RowId>> {
-    let key = key.to_owned();
-
-    Box::new(move |cell: Cell| {
-        if let Some((v, rowid)) = cell.index_payload() {
-            if rowid > last_rowid && v == key.as_str() {
-                return Some(rowid);
-            }
-        }
-        None
-    })
-}
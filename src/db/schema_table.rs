@@ -1,9 +1,8 @@
-use super::{cell::Cell, err, Error, PageNum, Result};
+use super::{cell::Cell, Error, PageNum, RecordValue, Result};
 
 #[derive(Debug)]
 pub struct Schema {
     r#type: String,
-    #[allow(unused)]
     name: String,
     tbl_name: String,
     rootpage: PageNum,
@@ -14,32 +13,35 @@ impl TryFrom<Cell> for Schema {
     type Error = Error;
 
     fn try_from(cell: Cell) -> std::result::Result<Self, Self::Error> {
-        assert!(
-            matches!(cell, Cell::LeafTable { .. }),
-            "The cell must be a Table Leaf"
-        );
+        if !matches!(cell, Cell::LeafTable { .. }) {
+            return Err(Error::corrupt("schema table must have table leaf cells"));
+        }
 
         let r#type = cell
             .column(0)
-            .ok_or(err!("Schema table must have column 0"))?
+            .ok_or_else(|| Error::corrupt("schema table must have column 0"))?
             .to_string();
         let name = cell
             .column(1)
-            .ok_or(err!("Schema table must have column 1"))?
+            .ok_or_else(|| Error::corrupt("schema table must have column 1"))?
             .to_string();
         let tbl_name = cell
             .column(2)
-            .ok_or(err!("Schema table must have column 2"))?
+            .ok_or_else(|| Error::corrupt("schema table must have column 2"))?
             .to_string();
         let rootpage = cell
             .column(3)
-            .ok_or(err!("Schema table must have column 3"))?
+            .ok_or_else(|| Error::corrupt("schema table must have column 3"))?
             .to_string()
             .parse::<PageNum>()?;
-        let sql = cell
+        // Indexes created for constraints have no SQL text.
+        let sql = match cell
             .column(4)
-            .ok_or(err!("Schema table must have column 4"))?
-            .to_string();
+            .ok_or_else(|| Error::corrupt("schema table must have column 4"))?
+        {
+            RecordValue::Null => String::new(),
+            value => value.to_string(),
+        };
 
         Ok(Self {
             r#type,
@@ -60,6 +62,10 @@ impl Schema {
         self.r#type.as_str()
     }
 
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
     pub fn tbl_name(&self) -> &str {
         self.tbl_name.as_str()
     }
@@ -1,23 +1,36 @@
 use super::{
-    cell::{Cell, RecordValue, RowId},
+    cell::{Affinity, Cell, Pager, Record, RecordValue, RowId},
+    cursor::{BtreeCursor, IndexCursor},
     err,
-    page::{BtreeIndexSearch, BtreeSearch},
     sql::{
+        create::{ColumnConstraint, ColumnDefinition, IndexedColumn, TableDefinition},
         parsers::{parse_create_index, parse_create_table},
-        Conditions,
+        Conditions, KeyRange,
     },
-    Db, Page, PageNum, Result, Schema,
+    Db, Error, PageNum, Result, Schema,
 };
-use once_cell::sync::Lazy;
-use regex::Regex;
-use std::io::{Read, Seek};
+use std::{
+    cmp::Ordering,
+    io::{Read, Seek, Write},
+};
+
+/// Names of the rowid in a table with one, unless a column has the name.
+const ROWID_NAMES: [&str; 3] = ["rowid", "oid", "_rowid_"];
 
 #[derive(Debug)]
 pub struct Table<'a, R: Read + Seek> {
     db_ref: &'a Db<R>,
     rootpage: PageNum,
     name: String,
-    columns: Vec<TableColumn>,
+    definition: TableDefinition,
+    /// Position in records of each column.
+    positions: Vec<usize>,
+    /// The column whose values are the rowids.
+    rowid_alias: Option<usize>,
+    /// The primary key of a `WITHOUT ROWID` table, whose index b-tree at the
+    /// root page holds the rows. It has no columns to seek by when the keys
+    /// are not in ascending order of their values.
+    clustered: Option<TableIndex>,
     indexes: Vec<TableIndex>,
 }
 
@@ -30,15 +43,45 @@ impl<'a, R: Read + Seek> Table<'a, R> {
         self.name.as_str()
     }
 
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns().iter().map(|col| col.name.as_str()).collect()
+    }
+
+    /// Whether the name refers to a column or to the rowid.
+    pub fn has_column(&self, name: &str) -> bool {
+        self.col_idx(name).is_some() || self.is_rowid_name(name)
+    }
+
     pub fn search_rows(&self, conditions: &Conditions) -> Result<TableSearch<'_, R>> {
-        match self.use_index(conditions) {
-            Some((index, key)) => self.index_search(index, key),
-            None => self.table_scan(),
+        if let Some(clustered) = &self.clustered {
+            let scan = self.index_scan(clustered, conditions).unwrap_or_default();
+            return self.clustered_search(scan);
+        }
+
+        let rowids = self.rowid_range(conditions);
+        if let Some(RecordValue::Int(rowid)) = rowids.as_ref().and_then(KeyRange::equal_value) {
+            let row = match RowId::try_from(*rowid) {
+                Ok(rowid) => self.get_row(rowid)?,
+                Err(_) => None,
+            };
+            return Ok(TableSearch::Row(row));
+        }
+
+        // Equality on indexed columns narrows rows down more than a range of
+        // rowids.
+        match (self.use_index(conditions), rowids) {
+            (Some((index, scan)), _) if !scan.prefix.is_empty() => self.index_search(index, scan),
+            (_, Some(range)) => self.rowid_search(range),
+            (Some((index, scan)), None) => self.index_search(index, scan),
+            (None, None) => self.table_scan(),
         }
     }
 
     pub fn get_row(&self, rowid: RowId) -> Result<Option<TableRow<'_, R>>> {
-        if let Some(row) = self.rows(Some(rowid))?.next() {
+        if self.clustered.is_some() {
+            return Ok(None);
+        }
+        if let Some(row) = self.rows(Some(rowid))?.next().transpose()? {
             if row.rowid().is_some_and(|id| id == rowid) {
                 return Ok(Some(row));
             }
@@ -50,37 +93,204 @@ impl<'a, R: Read + Seek> Table<'a, R> {
         Ok(TableSearch::Scan(self.rows(None)?))
     }
 
-    fn index_search(&'a self, index: &'a TableIndex, key: String) -> Result<TableSearch<'a, R>> {
+    /// Seeks the table b-tree to the first row which may be in the range.
+    fn rowid_search(&'a self, range: KeyRange) -> Result<TableSearch<'a, R>> {
+        let first = match range.lower_value() {
+            Some(RecordValue::Int(n)) => Some((*n).max(0) as RowId),
+            Some(RecordValue::Float(n)) => Some(n.max(0.0) as RowId),
+            Some(RecordValue::PrimaryKey(n)) => Some(*n),
+            _ => None,
+        };
+
+        Ok(TableSearch::Rowids(RowidRows {
+            rows: self.rows(first)?,
+            range,
+        }))
+    }
+
+    fn index_search(&'a self, index: &TableIndex, scan: IndexScan) -> Result<TableSearch<'a, R>> {
+        let mut cursor = IndexCursor::new(self.db_ref, index.rootpage)?;
+        cursor.seek(|cell| {
+            cell.index_payload()
+                .is_some_and(|(keys, _)| scan.is_below(keys))
+        })?;
+
         Ok(TableSearch::Index(IndexRows {
             table: self,
-            last_rowid: None,
-            key,
-            rootpage: self.db_ref.page(index.rootpage)?,
+            cursor,
+            scan,
+        }))
+    }
+
+    fn clustered_search(&'a self, scan: IndexScan) -> Result<TableSearch<'a, R>> {
+        let mut cursor = IndexCursor::new(self.db_ref, self.rootpage)?;
+        cursor.seek(|cell| cell.keys().is_some_and(|keys| scan.is_below(keys)))?;
+
+        Ok(TableSearch::Clustered(ClusteredRows {
+            table: self,
+            cursor,
+            scan,
         }))
     }
 
     fn rows(&self, rowid: Option<RowId>) -> Result<TableRows<'_, R>> {
+        let mut cursor = BtreeCursor::new(self.db_ref, self.rootpage)?;
+        if let Some(rowid) = rowid {
+            cursor.seek(rowid)?;
+        }
         Ok(TableRows {
             table: self,
-            rowid,
-            rootpage: self.rootpage()?,
+            cursor,
         })
     }
 
+    fn columns(&self) -> &[ColumnDefinition] {
+        &self.definition.columns
+    }
+
     fn col_idx(&self, name: &str) -> Option<usize> {
-        self.columns.iter().position(|col| col.name() == name)
+        self.columns()
+            .iter()
+            .position(|col| col.name.eq_ignore_ascii_case(name))
     }
 
-    fn primary_key(&self) -> Option<&TableColumn> {
-        self.columns.iter().find(|col| col.primary_key)
+    /// Whether the name refers to the rowid rather than to a column.
+    fn is_rowid_name(&self, name: &str) -> bool {
+        self.clustered.is_none()
+            && ROWID_NAMES.iter().any(|n| n.eq_ignore_ascii_case(name))
+            && self.col_idx(name).is_none()
     }
 
-    fn rootpage(&self) -> Result<Page> {
-        self.db_ref.page(self.rootpage)
+    /// The rowids to visit, from the conditions on the rowid alias column or
+    /// on one of the names of the rowid.
+    fn rowid_range(&self, conditions: &Conditions) -> Option<KeyRange> {
+        if self.clustered.is_some() {
+            return None;
+        }
+        let alias = self
+            .rowid_alias
+            .map(|idx| self.columns()[idx].name.as_str());
+        let names = ROWID_NAMES.into_iter().filter(|n| self.is_rowid_name(n));
+
+        alias
+            .into_iter()
+            .chain(names)
+            .find_map(|name| conditions.range(name, Affinity::Integer))
     }
 
-    fn use_index(&self, conditions: &Conditions) -> Option<(&TableIndex, String)> {
-        self.indexes.iter().find_map(|idx| idx.get_key(conditions))
+    /// Affinity of a column. The rowid has the integer affinity.
+    pub fn affinity(&self, name: &str) -> Affinity {
+        match self.col_idx(name) {
+            Some(idx) => self.columns()[idx].affinity(),
+            None => Affinity::Integer,
+        }
+    }
+
+    /// The index whose entries to visit are narrowed down the most by the
+    /// conditions, preferring equality on more leading columns.
+    fn use_index(&self, conditions: &Conditions) -> Option<(&TableIndex, IndexScan)> {
+        let mut best: Option<(&TableIndex, IndexScan)> = None;
+
+        for index in &self.indexes {
+            if let Some(scan) = self.index_scan(index, conditions) {
+                if best.as_ref().map_or(true, |(_, b)| scan.rank() > b.rank()) {
+                    best = Some((index, scan));
+                }
+            }
+        }
+
+        best
+    }
+
+    /// The entries of an index to visit, from equality on a leading prefix of
+    /// its columns and a range on the column after it.
+    fn index_scan(&self, index: &TableIndex, conditions: &Conditions) -> Option<IndexScan> {
+        let mut prefix = vec![];
+
+        for col in &index.columns {
+            let affinity = self.affinity(col);
+            let Some(range) = conditions.range(col, affinity) else {
+                break;
+            };
+            match range.equal_value() {
+                Some(value) => prefix.push(value.clone()),
+                None => return Some(IndexScan { prefix, range }),
+            }
+        }
+
+        (!prefix.is_empty()).then(|| IndexScan {
+            prefix,
+            range: KeyRange::default(),
+        })
+    }
+}
+
+impl<R: Read + Write + Seek> Table<'_, R> {
+    /// Inserts a row given as one value per table column and returns its
+    /// rowid. Without a value for the rowid alias column, the rowid is one more
+    /// than the largest rowid in the table.
+    pub fn insert(&self, values: Vec<RecordValue>) -> Result<RowId> {
+        if self.clustered.is_some() {
+            return Err(Error::Unsupported(format!(
+                "cannot insert into {}: it is a WITHOUT ROWID table",
+                self.name
+            )));
+        }
+        if !self.indexes.is_empty() {
+            return Err(Error::Unsupported(format!(
+                "cannot insert into {}: updating its indexes is not supported",
+                self.name
+            )));
+        }
+        if values.len() != self.columns().len() {
+            return Err(err!(
+                "table {} has {} columns but {} values were supplied",
+                self.name,
+                self.columns().len(),
+                values.len()
+            ));
+        }
+
+        // Values are stored as the type their column prefers when possible.
+        let mut values: Vec<RecordValue> = values
+            .into_iter()
+            .zip(self.columns())
+            .map(|(value, col)| value.apply_affinity(col.affinity()))
+            .collect();
+
+        let alias = self.rowid_alias;
+        let rowid = match alias.map(|idx| &values[idx]) {
+            Some(RecordValue::Int(n)) => RowId::try_from(*n)
+                .map_err(|_| Error::Unsupported("negative rowids are not supported".to_string()))?,
+            Some(RecordValue::Null) | None => {
+                let mut cursor = BtreeCursor::new(self.db_ref, self.rootpage)?;
+                match cursor.last()?.and_then(|cell| cell.rowid()) {
+                    Some(last) if last >= i64::MAX as RowId => {
+                        return Err(Error::Full);
+                    }
+                    Some(last) => last + 1,
+                    None => 1,
+                }
+            }
+            Some(_) => return Err(Error::Mismatch),
+        };
+
+        if self.get_row(rowid)?.is_some() {
+            let col = alias.map_or("rowid", |idx| self.columns()[idx].name.as_str());
+            return Err(Error::Constraint(format!(
+                "UNIQUE constraint failed: {}.{col}",
+                self.name
+            )));
+        }
+
+        // The rowid alias column is stored as NULL, its value being the rowid.
+        if let Some(idx) = alias {
+            values[idx] = RecordValue::Null;
+        }
+
+        let record = Record::encode(&values, self.db_ref.text_encoding()?);
+        self.db_ref.insert_record(self.rootpage, rowid, &record)?;
+        Ok(rowid)
     }
 }
 
@@ -113,7 +323,8 @@ impl<'a, R: Read + Seek> TableBuilder<'a, R> {
         let mut table_schema: Option<Schema> = None;
         let mut index_schemas: Vec<Schema> = vec![];
 
-        for schema in schemas.filter(|s| s.tbl_name() == self.name) {
+        // Names are case insensitive like other identifiers.
+        for schema in schemas.filter(|s| s.tbl_name().eq_ignore_ascii_case(self.name)) {
             if schema.r#type() == "table" {
                 table_schema = Some(schema);
             } else if schema.r#type() == "index" {
@@ -134,48 +345,112 @@ impl<'a, R: Read + Seek> TableBuilder<'a, R> {
             .ok_or(err!("A pointer to db is required to TableBuilder"))?;
         let table_schema = self
             .table_schema
-            .ok_or(err!("Not found \"{}\" table", self.name))?;
+            .ok_or_else(|| Error::NoSuchTable(self.name.to_string()))?;
         let rootpage = table_schema.rootpage();
         let sql = table_schema.sql();
-        let (_, (col_defs, _)) = parse_create_table(sql).map_err(|e| err!("{e}"))?;
-
-        let mut columns: Vec<TableColumn> = vec![];
-        for col_def in col_defs {
-            columns.push(TableColumn::new(col_def)?);
+        let (_, definition) = parse_create_table(sql).map_err(|_| unsupported_schema(sql))?;
+        // Their values are computed when read rather than stored in records.
+        let is_virtual =
+            |c: &ColumnConstraint| matches!(c, ColumnConstraint::Generated { stored: false, .. });
+        if let Some(col) = definition
+            .columns
+            .iter()
+            .find(|col| col.constraints.iter().any(is_virtual))
+        {
+            return Err(Error::Unsupported(format!(
+                "virtual generated column {}.{}",
+                self.name, col.name
+            )));
         }
 
+        let clustered = definition.without_rowid.then(|| {
+            let key = definition.primary_key();
+            TableIndex::new("primary key", seek_columns(&key), rootpage)
+        });
+
         let mut indexes: Vec<TableIndex> = vec![];
         for idx_schema in self.index_schemas {
             let sql = idx_schema.sql();
-            let (_, (cols, name)) = parse_create_index(sql).map_err(|e| err!("{e}"))?;
-            indexes.push(TableIndex::new(name, cols, idx_schema.rootpage()))
+            // Indexes created for constraints have no definition to read their
+            // columns from, and the entries of other indexes on a WITHOUT ROWID
+            // table end with the primary key rather than a rowid. They are
+            // never sought but still need updating.
+            if sql.is_empty() || clustered.is_some() {
+                indexes.push(TableIndex::new(
+                    idx_schema.name(),
+                    vec![],
+                    idx_schema.rootpage(),
+                ));
+                continue;
+            }
+            let (_, (key, name)) = parse_create_index(sql).map_err(|_| unsupported_schema(sql))?;
+            let key = definition.with_collations(key);
+            indexes.push(TableIndex::new(
+                name,
+                seek_columns(&key),
+                idx_schema.rootpage(),
+            ))
         }
 
         Ok(Table {
             db_ref,
             rootpage,
             name: self.name.into(),
-            columns,
+            positions: definition.record_positions(),
+            rowid_alias: definition.rowid_alias(),
+            definition,
+            clustered,
             indexes,
         })
     }
 }
 
+/// The columns of a key to seek entries by. Keys are only sought when they
+/// are in ascending `BINARY` order, the order values compare in, so there are
+/// none for a key in another order, and its entries are never sought.
+fn seek_columns(key: &[IndexedColumn]) -> Vec<&str> {
+    if key.iter().all(IndexedColumn::is_ascending_binary) {
+        key.iter().map(|col| col.name.as_str()).collect()
+    } else {
+        vec![]
+    }
+}
+
+pub(super) fn unsupported_schema(sql: &str) -> Error {
+    Error::Unsupported(format!("cannot parse the schema: {sql}"))
+}
+
 #[derive(Debug)]
 pub enum TableSearch<'a, R: Read + Seek> {
+    Row(Option<TableRow<'a, R>>),
     Scan(TableRows<'a, R>),
+    Rowids(RowidRows<'a, R>),
     Index(IndexRows<'a, R>),
+    Clustered(ClusteredRows<'a, R>),
 }
 
 impl<'a, R: Read + Seek> Iterator for TableSearch<'a, R> {
-    type Item = TableRow<'a, R>;
+    type Item = Result<TableRow<'a, R>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
+            Self::Row(row) => row.take().map(Ok),
             Self::Scan(scan) => scan.next(),
-            Self::Index(index) => index
-                .next()
-                .and_then(|rowid| index.table.get_row(rowid).unwrap()),
+            Self::Rowids(rowids) => rowids.next(),
+            Self::Clustered(clustered) => clustered.next(),
+            Self::Index(index) => {
+                let rowid = match index.next()? {
+                    Ok(rowid) => rowid,
+                    Err(e) => return Some(Err(e)),
+                };
+                // Every index entry refers to a row of its table.
+                let row = index.table.get_row(rowid).and_then(|row| {
+                    row.ok_or_else(|| {
+                        Error::corrupt(format!("index entry refers to missing row {rowid}"))
+                    })
+                });
+                Some(row)
+            }
         }
     }
 }
@@ -183,72 +458,145 @@ impl<'a, R: Read + Seek> Iterator for TableSearch<'a, R> {
 #[derive(Debug)]
 pub struct TableRows<'a, R: Read + Seek> {
     table: &'a Table<'a, R>,
-    rowid: Option<RowId>,
-    rootpage: Page,
+    cursor: BtreeCursor<'a, R>,
 }
 
 impl<'a, R: Read + Seek> Iterator for TableRows<'a, R> {
-    type Item = TableRow<'a, R>;
+    type Item = Result<TableRow<'a, R>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let rowid = self.rowid.take().unwrap_or(RowId::MIN);
-        let mut search = self.rootpage.btree_scan(rowid).unwrap();
-
-        while let BtreeSearch::Pointer(p) = search {
-            search = self
-                .table
-                .db_ref
-                .page(p)
-                .and_then(|mut page| page.btree_scan(rowid))
-                .unwrap();
-        }
+        self.cursor
+            .next_cell()
+            .transpose()
+            .map(|cell| cell.map(|cell| TableRow::new(self.table, cell)))
+    }
+}
 
-        if let BtreeSearch::Leaf(Some(cell)) = search {
-            if let Some(found_rowid) = cell.rowid() {
-                if found_rowid >= rowid {
-                    self.rowid = Some(found_rowid + 1);
-                    return Some(TableRow::new(self.table, cell));
-                }
+/// Rows whose rowids are in a range, which end at the first row past it.
+#[derive(Debug)]
+pub struct RowidRows<'a, R: Read + Seek> {
+    rows: TableRows<'a, R>,
+    range: KeyRange,
+}
+
+impl<'a, R: Read + Seek> Iterator for RowidRows<'a, R> {
+    type Item = Result<TableRow<'a, R>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = match self.rows.next()? {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            let Some(rowid) = row.rowid() else {
+                return Some(Err(Error::corrupt("table row has no rowid")));
+            };
+            let key = RecordValue::PrimaryKey(rowid);
+            if self.range.is_above(&key) {
+                return None;
+            }
+            if !self.range.is_below(&key) {
+                return Some(Ok(row));
             }
         }
-        None
     }
 }
 
+/// Rows of a `WITHOUT ROWID` table from the index b-tree holding them, which
+/// end at the first row past the scan of its primary key.
 #[derive(Debug)]
-pub struct IndexRows<'a, R: Read + Seek> {
+pub struct ClusteredRows<'a, R: Read + Seek> {
     table: &'a Table<'a, R>,
-    last_rowid: Option<RowId>,
-    key: String,
-    rootpage: Page,
+    cursor: IndexCursor<'a, R>,
+    scan: IndexScan,
 }
 
-impl<R: Read + Seek> Iterator for IndexRows<'_, R> {
-    type Item = RowId;
+impl<'a, R: Read + Seek> Iterator for ClusteredRows<'a, R> {
+    type Item = Result<TableRow<'a, R>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let last_rowid = self.last_rowid.take().unwrap_or(RowId::MIN);
-        let mut search = self.rootpage.btree_search(last_rowid, &self.key).unwrap();
-        let mut rowid_in_iterior = None;
-
-        while let BtreeIndexSearch::PointerOrRowId(p, rowid) = search {
-            rowid_in_iterior = rowid;
-            search = self
-                .table
-                .db_ref
-                .page(p)
-                .and_then(|mut page| page.btree_search(last_rowid, &self.key))
-                .unwrap();
+        let cell = match self.cursor.next_cell().transpose()? {
+            Ok(cell) => cell,
+            Err(e) => return Some(Err(e)),
+        };
+        let Some(keys) = cell.keys() else {
+            return Some(Err(Error::corrupt("table row is not an index entry")));
+        };
+        if self.scan.is_above(keys) {
+            return None;
+        }
+        Some(Ok(TableRow::new(self.table, cell)))
+    }
+}
+
+/// The index entries to visit: those whose leading key columns equal the
+/// prefix and whose next column is in the range.
+#[derive(Debug, Default)]
+pub struct IndexScan {
+    prefix: Vec<RecordValue>,
+    range: KeyRange,
+}
+
+impl IndexScan {
+    /// Whether the entry with these keys comes before the ones to visit.
+    fn is_below(&self, keys: &[RecordValue]) -> bool {
+        match self.compare_prefix(keys) {
+            Ordering::Less => true,
+            Ordering::Greater => false,
+            Ordering::Equal => keys
+                .get(self.prefix.len())
+                .is_some_and(|key| self.range.is_below(key)),
         }
+    }
 
-        if let BtreeIndexSearch::RowId(rowid_opt) = search {
-            if let Some(rowid) = rowid_opt.or(rowid_in_iterior) {
-                self.last_rowid = Some(rowid);
-                return Some(rowid);
-            }
+    /// Whether the entry with these keys comes after the ones to visit.
+    fn is_above(&self, keys: &[RecordValue]) -> bool {
+        match self.compare_prefix(keys) {
+            Ordering::Less => false,
+            Ordering::Greater => true,
+            Ordering::Equal => keys
+                .get(self.prefix.len())
+                .is_some_and(|key| self.range.is_above(key)),
         }
+    }
+
+    fn compare_prefix(&self, keys: &[RecordValue]) -> Ordering {
+        keys.iter()
+            .zip(&self.prefix)
+            .map(|(key, value)| key.cmp(value))
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
 
-        None
+    fn rank(&self) -> (usize, bool) {
+        (self.prefix.len(), self.range.is_bounded())
+    }
+}
+
+/// Rowids from the index entries to visit, which end at the first entry past
+/// them.
+#[derive(Debug)]
+pub struct IndexRows<'a, R: Read + Seek> {
+    table: &'a Table<'a, R>,
+    cursor: IndexCursor<'a, R>,
+    scan: IndexScan,
+}
+
+impl<R: Read + Seek> Iterator for IndexRows<'_, R> {
+    type Item = Result<RowId>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cell = match self.cursor.next_cell().transpose()? {
+            Ok(cell) => cell,
+            Err(e) => return Some(Err(e)),
+        };
+        let Some((keys, rowid)) = cell.index_payload() else {
+            return Some(Err(Error::corrupt("index entry has no rowid")));
+        };
+        if self.scan.is_above(keys) {
+            return None;
+        }
+        Some(Ok(rowid))
     }
 }
 
@@ -263,60 +611,41 @@ impl<'a, R: Read + Seek> TableRow<'a, R> {
         Self { table, cell }
     }
 
+    /// The value of a column, or the rowid for the rowid alias column and the
+    /// names of the rowid.
     pub fn col(&self, name: &str) -> Result<RecordValue> {
-        match self.table.primary_key() {
-            Some(key) if key.name() == name && key.is_rowid() => self
-                .cell
+        let rowid = || {
+            self.cell
                 .rowid()
                 .map(RecordValue::PrimaryKey)
-                .ok_or(err!("Invalid primary key")),
-            _ => self
-                .table
-                .col_idx(name)
-                .and_then(|idx| self.cell.column(idx))
-                .ok_or(err!("Invalid column name: {name}")),
-        }
-    }
-
-    pub fn rowid(&self) -> Option<RowId> {
-        self.cell.rowid()
-    }
-}
-
-static COL_DEF: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r#"(?<name>(\w+|"(\w|\s)+"))\s+(?<ty>\w+)"#).unwrap());
-
-#[derive(Debug, PartialEq)]
-pub struct TableColumn {
-    r#type: String,
-    name: String,
-    primary_key: bool,
-}
-
-impl TableColumn {
-    pub fn new(def: &str) -> Result<Self> {
-        match COL_DEF.captures(def) {
-            Some(caps) => {
-                let name = &caps["name"].trim_matches('"');
-                let r#type = &caps["ty"];
-                let primary_key = def.contains("primary key");
-
-                Ok(Self {
-                    r#type: r#type.to_string(),
-                    name: name.to_string(),
-                    primary_key,
-                })
+                .ok_or_else(|| err!("Invalid primary key"))
+        };
+
+        match self.table.col_idx(name) {
+            Some(idx) if self.table.rowid_alias == Some(idx) => rowid(),
+            None if self.table.is_rowid_name(name) => rowid(),
+            None => Err(Error::NoSuchColumn(name.to_string())),
+            Some(idx) => {
+                let value = self
+                    .cell
+                    .column(self.table.positions[idx])
+                    .ok_or_else(|| err!("Invalid column name: {name}"))?;
+                // Real values without a fractional part may be stored as
+                // integers.
+                match self.table.columns()[idx].affinity() {
+                    Affinity::Real => Ok(value.apply_affinity(Affinity::Real)),
+                    _ => Ok(value),
+                }
             }
-            None => Err(err!("Cannot parse table column. {def}")),
         }
     }
 
-    fn name(&self) -> &str {
-        self.name.as_str()
+    pub fn affinity(&self, name: &str) -> Affinity {
+        self.table.affinity(name)
     }
 
-    fn is_rowid(&self) -> bool {
-        self.r#type.to_lowercase().as_str() == "integer" && self.primary_key
+    pub fn rowid(&self) -> Option<RowId> {
+        self.cell.rowid()
     }
 }
 
@@ -336,57 +665,240 @@ impl TableIndex {
             rootpage,
         }
     }
-
-    fn cols(&self) -> Vec<&str> {
-        self.columns.iter().map(|s| s.as_str()).collect()
-    }
-
-    fn get_key(&self, conditions: &Conditions) -> Option<(&Self, String)> {
-        if self.cols() == conditions.cols() {
-            conditions.values().first().map(|&v| (self, v.into()))
-        } else {
-            None
-        }
-    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::{
+        file_header::TextEncoding,
+        test_utils::{conditions, index_page, sample_db},
+    };
 
     #[test]
-    fn it_creates_table_column_from_string() {
-        let def = "name text";
-        let col = TableColumn::new(def).unwrap();
-        assert_eq!(
-            col,
-            TableColumn {
-                r#type: "text".into(),
-                name: "name".into(),
-                primary_key: false,
-            }
-        );
+    fn it_only_seeks_keys_in_binary_order() {
+        // An empty index leaf page for the indexes to be rooted at.
+        let db = sample_db(&[index_page(&[], None)]);
+        let indexes = [
+            (
+                "i_name",
+                "CREATE INDEX i_name ON apples (name COLLATE NOCASE)",
+            ),
+            ("i_color", "CREATE INDEX i_color ON apples (color DESC)"),
+        ];
+        for (rowid, (name, sql)) in (4..).zip(indexes) {
+            let values = ["index", name, "apples"]
+                .map(|text| RecordValue::Text(text.into()))
+                .into_iter()
+                .chain([RecordValue::Int(5), RecordValue::Text(sql.into())])
+                .collect::<Vec<_>>();
+            let record = Record::encode(&values, TextEncoding::Utf8);
+            db.insert_record(1, rowid, &record).unwrap();
+        }
 
-        let def = "id integer primary key autoincrement";
-        let col = TableColumn::new(def).unwrap();
-        assert_eq!(
-            col,
-            TableColumn {
-                r#type: "integer".into(),
-                name: "id".into(),
-                primary_key: true,
-            }
-        );
+        let table = db.table("apples").unwrap();
+        assert_eq!(table.indexes.len(), 2);
+        for clause in ["name < 'H'", "color = 'Red'"] {
+            let conditions = conditions(&format!("select * from apples where {clause}"));
+            assert!(matches!(
+                table.search_rows(&conditions).unwrap(),
+                TableSearch::Scan(_)
+            ));
+        }
+
+        // A key column is compared by the collation declared on the column.
+        let definition = parse_create_table("create table t (s text collate nocase, k)")
+            .unwrap()
+            .1;
+        let key = definition.with_collations(vec![IndexedColumn {
+            name: "s".into(),
+            collation: None,
+            desc: false,
+        }]);
+        assert!(seek_columns(&key).is_empty());
+    }
+
+    #[test]
+    fn it_returns_errors_from_corrupt_pages() {
+        let mut bytes = std::fs::read("sample.db").unwrap();
+        // The pointer to the second cell of the oranges page is out of it.
+        bytes[3 * 4096 + 10] = 0xff;
+        bytes[3 * 4096 + 11] = 0xff;
+        let db = Db::new(std::io::Cursor::new(bytes));
+        let table = db.table("oranges").unwrap();
+
+        let mut rows = table.rows(None).unwrap();
+        assert_eq!(rows.next().unwrap().unwrap().rowid(), Some(1));
+        assert!(matches!(
+            rows.next(),
+            Some(Err(Error::Corrupt { page: Some(4), .. }))
+        ));
+        assert!(table.get_row(2).is_err());
+    }
+
+    #[test]
+    fn it_reads_rows_of_without_rowid_tables() {
+        // Rows start with the primary key, and are ordered by it.
+        let rows = [("a", 3), ("b", 1), ("c", 2)]
+            .map(|(k, v)| (None, vec![RecordValue::Text(k.into()), RecordValue::Int(v)]));
+        let db = sample_db(&[index_page(&rows, None)]);
+
+        let definition =
+            parse_create_table("create table t (v integer, k text primary key) without rowid")
+                .unwrap()
+                .1;
+        let table = Table {
+            db_ref: &db,
+            rootpage: 5,
+            name: "t".into(),
+            positions: definition.record_positions(),
+            rowid_alias: definition.rowid_alias(),
+            definition,
+            clustered: Some(TableIndex::new("primary key", vec!["k"], 5)),
+            indexes: vec![],
+        };
+        let values = |clause: &str| {
+            let conditions = conditions(&format!("select * from t where {clause}"));
+            table
+                .search_rows(&conditions)
+                .unwrap()
+                .map(|row| {
+                    let row = row.unwrap();
+                    format!("{}{}", row.col("k").unwrap(), row.col("v").unwrap())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(values("v > 0"), vec!["a3", "b1", "c2"]);
+        assert_eq!(values("k = 'b'"), vec!["b1"]);
+        assert_eq!(values("k > 'a' and v < 3"), vec!["b1", "c2"]);
+        assert!(table.get_row(1).unwrap().is_none());
+        let row = table.search_rows(&Conditions::default()).unwrap().next();
+        assert!(matches!(
+            row.unwrap().unwrap().col("rowid"),
+            Err(Error::NoSuchColumn(_))
+        ));
+        assert!(matches!(
+            table.insert(vec![RecordValue::Int(4), RecordValue::Text("d".into())]),
+            Err(Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn it_keeps_indexes_of_without_rowid_tables_unsought() {
+        let sql = "create table t (k text primary key, v integer) without rowid";
+        let db = Db::sample_with_table("t", sql, &[]).unwrap();
+        let values = ["index", "i_v", "t"]
+            .map(|text| RecordValue::Text(text.into()))
+            .into_iter()
+            .chain([
+                RecordValue::Int(6),
+                RecordValue::Text("create index i_v on t (v)".into()),
+            ])
+            .collect::<Vec<_>>();
+        let record = Record::encode(&values, TextEncoding::Utf8);
+        db.insert_record(1, 5, &record).unwrap();
+
+        let table = db.table("t").unwrap();
+        assert_eq!(table.indexes.len(), 1);
+        assert!(table.indexes[0].columns.is_empty());
+        assert!(matches!(
+            table.insert(vec![RecordValue::Text("a".into()), RecordValue::Int(1)]),
+            Err(Error::Unsupported(_))
+        ));
+    }
 
-        let def = "\"size range\" text";
-        let col = TableColumn::new(def).unwrap();
+    #[test]
+    fn it_does_not_insert_into_tables_with_autoindexes() {
+        // An empty index leaf page for the index to be rooted at.
+        let db = sample_db(&[index_page(&[], None)]);
+        // Like the index sqlite creates for `e text unique`.
+        let values = ["index", "sqlite_autoindex_oranges_1", "oranges"]
+            .map(|text| RecordValue::Text(text.into()))
+            .into_iter()
+            .chain([RecordValue::Int(5), RecordValue::Null])
+            .collect::<Vec<_>>();
+        let record = Record::encode(&values, TextEncoding::Utf8);
+        db.insert_record(1, 4, &record).unwrap();
+
+        let insert = crate::sql::Sql::new("insert into oranges (name) values ('Yuzu')").unwrap();
+        assert!(matches!(
+            insert.execute(&db),
+            Err(Error::Unsupported(msg)) if msg.contains("indexes")
+        ));
+        let table = db.table("oranges").unwrap();
+        let conditions = conditions("select * from oranges where name = 'Yuzu'");
+        assert!(matches!(
+            table.search_rows(&conditions).unwrap(),
+            TableSearch::Scan(_)
+        ));
+        assert_eq!(table.rows(None).unwrap().count(), 6);
+    }
+
+    #[test]
+    fn it_scans_index_entries_by_prefix_and_range() {
+        let db = crate::db::DbFile::from_path("sample.db").unwrap();
+        let definition = parse_create_table("create table t (a text, b integer, c text)")
+            .unwrap()
+            .1;
+        let table = Table {
+            db_ref: &db,
+            rootpage: 2,
+            name: "t".into(),
+            positions: definition.record_positions(),
+            rowid_alias: definition.rowid_alias(),
+            definition,
+            clustered: None,
+            indexes: vec![
+                TableIndex::new("i_c", vec!["c"], 3),
+                TableIndex::new("i_a_b", vec!["a", "b"], 4),
+            ],
+        };
+        let conditions = |clause: &str| conditions(&format!("select * from t where {clause}"));
+        let keys = |a: &str, b: i64| [RecordValue::Text(a.into()), RecordValue::Int(b)];
+
+        let (index, scan) = table
+            .use_index(&conditions("c = 'z' and b > 1 and b <= 3 and a = 'x'"))
+            .unwrap();
+        assert_eq!(index.name, "i_a_b");
+        assert!(scan.is_below(&keys("w", 9)));
+        assert!(scan.is_below(&keys("x", 1)));
+        assert!(!scan.is_below(&keys("x", 2)));
+        assert!(!scan.is_above(&keys("x", 3)));
+        assert!(scan.is_above(&keys("x", 4)));
+        assert!(scan.is_above(&keys("y", 0)));
+
+        let (index, scan) = table.use_index(&conditions("a = 'x' and c = 'z'")).unwrap();
+        assert_eq!(index.name, "i_c");
+        assert_eq!(scan.rank(), (1, false));
+
+        // Only a leading column can be used.
+        assert!(table.use_index(&conditions("b = 1")).is_none());
+    }
+
+    #[test]
+    fn it_seeks_rows_by_rowid() -> Result<()> {
+        let db = crate::db::DbFile::from_path("sample.db")?;
+        let oranges = db.table("oranges")?;
+        let search = |clause: &str| -> Result<Vec<String>> {
+            let conditions = conditions(&format!("select * from oranges where {clause}"));
+            let rows = oranges.search_rows(&conditions)?;
+            let single = matches!(rows, TableSearch::Row(_));
+            rows.map(|row| Ok(format!("{single}:{}", row?.col("name")?)))
+                .collect()
+        };
+
+        assert_eq!(search("id = 2")?, vec!["true:Tangelo"]);
+        assert_eq!(search("OID = '3'")?, vec!["true:Tangerine"]);
+        assert!(search("_rowid_ = 9")?.is_empty());
         assert_eq!(
-            col,
-            TableColumn {
-                r#type: "text".into(),
-                name: "size range".into(),
-                primary_key: false,
-            }
+            search("rowid > 3.5 and rowid <= 5")?,
+            vec!["false:Clementine", "false:Valencia Orange"]
         );
+
+        let row = oranges.get_row(6)?.unwrap();
+        assert_eq!(row.col("rowid")?, RecordValue::Int(6));
+        assert_eq!(row.col("_ROWID_")?, row.col("id")?);
+        Ok(())
     }
 }
@@ -0,0 +1,45 @@
+//! Databases, pages and clauses shared by the tests.
+
+use super::{cell::Record, file_header::TextEncoding, sql::Conditions, Db, PageNum, RecordValue};
+use std::io::Cursor;
+
+/// The sample database in memory, followed by the given pages.
+pub(crate) fn sample_db(pages: &[Vec<u8>]) -> Db<Cursor<Vec<u8>>> {
+    let mut bytes = std::fs::read("sample.db").unwrap();
+    bytes.extend(pages.concat());
+    Db::new(Cursor::new(bytes))
+}
+
+/// Lays out an index b-tree page with a cell for each record, interior cells
+/// pointing to their left child. It is a leaf without a right-most child.
+pub(crate) fn index_page(
+    cells: &[(Option<PageNum>, Vec<RecordValue>)],
+    right_most: Option<PageNum>,
+) -> Vec<u8> {
+    let mut page = vec![0u8; 4096];
+    let header_size = if right_most.is_some() { 12 } else { 8 };
+    page[0] = if right_most.is_some() { 0x02 } else { 0x0a };
+    page[3..5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    if let Some(right_most) = right_most {
+        page[8..12].copy_from_slice(&right_most.to_be_bytes());
+    }
+
+    let mut content_start = page.len();
+    for (i, (left, values)) in cells.iter().enumerate() {
+        let payload = Record::encode(values, TextEncoding::Utf8);
+        let mut cell = left.map(|l| l.to_be_bytes().to_vec()).unwrap_or_default();
+        cell.push(payload.len() as u8);
+        cell.extend(payload);
+
+        content_start -= cell.len();
+        page[content_start..content_start + cell.len()].copy_from_slice(&cell);
+        let pointer = header_size + 2 * i;
+        page[pointer..pointer + 2].copy_from_slice(&(content_start as u16).to_be_bytes());
+    }
+    page
+}
+
+/// The conditions of the WHERE clause of a select statement.
+pub(crate) fn conditions(sql: &str) -> Conditions {
+    crate::sql::parsers::parse_select(sql).unwrap().1.conditions
+}
@@ -35,6 +35,28 @@ impl Varint {
     pub fn byte_len(&self) -> usize {
         self.byte_len
     }
+
+    /// Encodes a value with 7 bits per byte, most significant first, where a
+    /// ninth byte carries all of its 8 bits.
+    pub fn encode(value: u64) -> Vec<u8> {
+        if value >> 56 != 0 {
+            let mut bytes: Vec<u8> = (1..=8)
+                .rev()
+                .map(|i| ((value >> (i * 7 + 1)) & 0x7f) as u8 | 0x80)
+                .collect();
+            bytes.push(value as u8);
+            return bytes;
+        }
+
+        let mut bytes = vec![(value & 0x7f) as u8];
+        let mut rest = value >> 7;
+        while rest > 0 {
+            bytes.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        bytes.reverse();
+        bytes
+    }
 }
 
 fn read_one_byte<R: Read>(r: &mut R) -> Result<u8> {
@@ -80,4 +102,17 @@ mod tests {
         };
         assert_eq!(Varint::new(&mut cursor).unwrap(), expected);
     }
+
+    #[test]
+    fn it_encodes_values() {
+        assert_eq!(Varint::encode(7), vec![0x07]);
+        assert_eq!(Varint::encode(199), vec![0x81, 0x47]);
+
+        for value in [0, 127, 128, 16383, 16384, 1 << 40] {
+            let bytes = Varint::encode(value);
+            let decoded = Varint::new(&mut Cursor::new(&bytes)).unwrap();
+            assert_eq!(decoded.value(), value);
+            assert_eq!(decoded.byte_len(), bytes.len());
+        }
+    }
 }
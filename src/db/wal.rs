@@ -0,0 +1,275 @@
+use super::{Error, PageNum, Result};
+use std::{
+    collections::HashMap,
+    io::{Read, Seek, SeekFrom},
+    sync::{Mutex, MutexGuard},
+};
+
+const WAL_HEADER_SIZE: usize = 32;
+const FRAME_HEADER_SIZE: usize = 24;
+/// The least significant bit tells whether checksums read big-endian words.
+const MAGIC: u32 = 0x377f0682;
+const FORMAT_VERSION: u32 = 3007000;
+
+/// A write-ahead log, whose committed frames hold newer versions of pages than
+/// the database file.
+///
+/// Like SQLite recovering a log, frames are read up to the first one with
+/// mismatching salts or checksums, and only frames up to the last commit frame
+/// are used.
+#[derive(Debug)]
+pub struct Wal<R: Read + Seek> {
+    r: Mutex<R>,
+    /// Offset of the newest committed content of each page.
+    frames: HashMap<PageNum, u64>,
+    db_size: Option<u32>,
+}
+
+impl<R: Read + Seek> Wal<R> {
+    /// Indexes the log. A log without a valid header has no frames.
+    pub fn new(mut r: R) -> Result<Self> {
+        let len = r.seek(SeekFrom::End(0))?;
+        let mut wal_header = [0u8; WAL_HEADER_SIZE];
+        let mut frames = HashMap::new();
+        let mut db_size = None;
+
+        if len >= WAL_HEADER_SIZE as u64 {
+            r.seek(SeekFrom::Start(0))?;
+            r.read_exact(&mut wal_header)?;
+        }
+
+        if let Some(header) = WalHeader::new(&wal_header) {
+            let mut pending = HashMap::new();
+            let (mut s0, mut s1) = (header.checksum[0], header.checksum[1]);
+            let mut offset = WAL_HEADER_SIZE as u64;
+            let frame_size = (FRAME_HEADER_SIZE + header.page_size) as u64;
+            let mut frame = vec![0u8; FRAME_HEADER_SIZE + header.page_size];
+
+            while offset + frame_size <= len {
+                r.seek(SeekFrom::Start(offset))?;
+                r.read_exact(&mut frame)?;
+
+                let page_num = read_u32(&frame, 0, true);
+                let commit_size = read_u32(&frame, 4, true);
+                if frame[8..16] != wal_header[16..24] {
+                    break;
+                }
+
+                (s0, s1) = checksum(&frame[..8], s0, s1, header.big_endian);
+                (s0, s1) = checksum(&frame[FRAME_HEADER_SIZE..], s0, s1, header.big_endian);
+                if [s0, s1] != [read_u32(&frame, 16, true), read_u32(&frame, 20, true)] {
+                    break;
+                }
+
+                pending.insert(page_num, offset + FRAME_HEADER_SIZE as u64);
+                if commit_size > 0 {
+                    frames.extend(pending.drain());
+                    db_size = Some(commit_size);
+                }
+                offset += frame_size;
+            }
+        }
+
+        Ok(Self {
+            r: Mutex::new(r),
+            frames,
+            db_size,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Size of the database in pages after the last committed transaction.
+    pub fn db_size(&self) -> Option<u32> {
+        self.db_size
+    }
+
+    /// Fills the buffer from the start of the newest committed version of the
+    /// page, and tells whether the log has one.
+    pub fn read_page(&self, num: PageNum, buf: &mut [u8]) -> Result<bool> {
+        let Some(&offset) = self.frames.get(&num) else {
+            return Ok(false);
+        };
+
+        let mut r = self.lock()?;
+        r.seek(SeekFrom::Start(offset))?;
+        r.read_exact(buf)?;
+        Ok(true)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, R>> {
+        self.r.lock().map_err(Error::from)
+    }
+}
+
+#[derive(Debug)]
+struct WalHeader {
+    big_endian: bool,
+    page_size: usize,
+    checksum: [u32; 2],
+}
+
+impl WalHeader {
+    /// Reads the header, or nothing when it is not a valid one.
+    fn new(bytes: &[u8; WAL_HEADER_SIZE]) -> Option<Self> {
+        let magic = read_u32(bytes, 0, true);
+        if magic & !1 != MAGIC || read_u32(bytes, 4, true) != FORMAT_VERSION {
+            return None;
+        }
+
+        let page_size = read_u32(bytes, 8, true) as usize;
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return None;
+        }
+
+        let big_endian = magic & 1 == 1;
+        let checksum = [read_u32(bytes, 24, true), read_u32(bytes, 28, true)];
+        let (s0, s1) = self::checksum(&bytes[..24], 0, 0, big_endian);
+        if [s0, s1] != checksum {
+            return None;
+        }
+
+        Some(Self {
+            big_endian,
+            page_size,
+            checksum,
+        })
+    }
+}
+
+/// The running checksum of the log, adding up pairs of 32-bit words.
+fn checksum(bytes: &[u8], mut s0: u32, mut s1: u32, big_endian: bool) -> (u32, u32) {
+    for i in (0..bytes.len() - bytes.len() % 8).step_by(8) {
+        let x0 = read_u32(bytes, i, big_endian);
+        let x1 = read_u32(bytes, i + 4, big_endian);
+        s0 = s0.wrapping_add(x0).wrapping_add(s1);
+        s1 = s1.wrapping_add(x1).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let word = [
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ];
+    if big_endian {
+        u32::from_be_bytes(word)
+    } else {
+        u32::from_le_bytes(word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Db, RecordValue};
+    use std::io::Cursor;
+
+    /// Builds a log with the given frames, each a page number, the page content
+    /// and the database size for a commit frame.
+    fn wal(page_size: usize, frames: &[(PageNum, &[u8], u32)]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend((MAGIC | 1).to_be_bytes());
+        bytes.extend(FORMAT_VERSION.to_be_bytes());
+        bytes.extend((page_size as u32).to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+        bytes.extend([0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0]);
+        let (mut s0, mut s1) = checksum(&bytes, 0, 0, true);
+        bytes.extend(s0.to_be_bytes());
+        bytes.extend(s1.to_be_bytes());
+
+        for (page_num, page, commit_size) in frames {
+            let mut frame = vec![];
+            frame.extend(page_num.to_be_bytes());
+            frame.extend(commit_size.to_be_bytes());
+            frame.extend_from_slice(&bytes[16..24]);
+            (s0, s1) = checksum(&frame[..8], s0, s1, true);
+            (s0, s1) = checksum(page, s0, s1, true);
+            frame.extend(s0.to_be_bytes());
+            frame.extend(s1.to_be_bytes());
+            frame.extend_from_slice(page);
+            bytes.extend(frame);
+        }
+
+        bytes
+    }
+
+    fn read(wal: &Wal<Cursor<Vec<u8>>>, num: PageNum) -> Option<u8> {
+        let mut buf = [0u8; 1];
+        wal.read_page(num, &mut buf).unwrap().then_some(buf[0])
+    }
+
+    #[test]
+    fn it_indexes_committed_frames() {
+        let bytes = wal(
+            512,
+            &[
+                (2, &[1; 512], 0),
+                (3, &[1; 512], 5),
+                (2, &[2; 512], 6),
+                (4, &[2; 512], 0),
+            ],
+        );
+        let wal = Wal::new(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(read(&wal, 2), Some(2));
+        assert_eq!(read(&wal, 3), Some(1));
+        // The last frame was never committed.
+        assert_eq!(read(&wal, 4), None);
+        assert_eq!(wal.db_size(), Some(6));
+    }
+
+    #[test]
+    fn it_stops_at_an_invalid_checksum() {
+        let mut bytes = wal(512, &[(2, &[1; 512], 3), (3, &[1; 512], 3)]);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        let wal = Wal::new(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(read(&wal, 2), Some(1));
+        assert_eq!(read(&wal, 3), None);
+    }
+
+    #[test]
+    fn it_reads_pages_from_the_log() {
+        let bytes = std::fs::read("sample.db").unwrap();
+
+        // Page 4 with a new row, as a commit would have logged it.
+        let changed = Db::new(Cursor::new(bytes.clone()));
+        let oranges = changed.table("oranges").unwrap();
+        oranges
+            .insert(vec![
+                RecordValue::Null,
+                RecordValue::Text("Yuzu".into()),
+                RecordValue::Null,
+            ])
+            .unwrap();
+        let page = changed.page_buffer(4).unwrap();
+        let page_count = changed.page_count().unwrap();
+
+        let log = wal(4096, &[(4, page.as_ref(), page_count)]);
+        let db = Db::new(Cursor::new(bytes))
+            .with_wal(Cursor::new(log))
+            .unwrap();
+        let oranges = db.table("oranges").unwrap();
+        let row = oranges.get_row(7).unwrap().unwrap();
+        assert_eq!(row.col("name").unwrap().to_string(), "Yuzu");
+
+        // Writing to the file would lose the logged changes.
+        assert!(oranges.insert(vec![RecordValue::Null; 3]).is_err());
+    }
+
+    #[test]
+    fn it_ignores_a_log_with_an_invalid_header() {
+        let mut bytes = wal(512, &[(2, &[1; 512], 3)]);
+        bytes[12] ^= 0xff;
+        assert!(Wal::new(Cursor::new(bytes)).unwrap().is_empty());
+
+        assert!(Wal::new(Cursor::new(vec![])).unwrap().is_empty());
+    }
+}
@@ -1,7 +1,8 @@
-use super::err;
+use super::{db::PageNum, err};
+use nom::error::ErrorKind;
 use std::io;
 use std::num::ParseIntError;
-use std::string::FromUtf8Error;
+use std::string::{FromUtf16Error, FromUtf8Error};
 use std::sync::{MutexGuard, PoisonError};
 use thiserror::Error as ThisError;
 
@@ -13,21 +14,136 @@ pub enum Error {
     #[error("ERR - from_utf8: {0}")]
     FromUtf8(#[from] FromUtf8Error),
 
+    #[error("ERR - from_utf16: {0}")]
+    FromUtf16(#[from] FromUtf16Error),
+
     #[error("ERR - parse int: {0}")]
     ParseInt(#[from] ParseIntError),
 
+    /// The database file does not follow the file format, on the given page
+    /// when it is known.
+    #[error("ERR - corrupt: database disk image is malformed{}: {detail}", on_page(.page))]
+    Corrupt {
+        page: Option<PageNum>,
+        detail: String,
+    },
+
+    #[error("ERR - no such table: {0}")]
+    NoSuchTable(String),
+
+    #[error("ERR - no such column: {0}")]
+    NoSuchColumn(String),
+
+    /// A statement that is not valid SQL, with the byte offset in the statement
+    /// where parsing stopped.
+    #[error("ERR - parse: syntax error at offset {position}: expected {expected}")]
+    Parse { position: usize, expected: String },
+
+    /// Valid SQL or a valid database using a feature which is not implemented.
+    #[error("ERR - unsupported: {0}")]
+    Unsupported(String),
+
+    #[error("ERR - constraint: {0}")]
+    Constraint(String),
+
+    /// No rowid is left for a new row.
+    #[error("ERR - full: database or disk is full")]
+    Full,
+
+    /// A value of the wrong type for a column, such as a rowid that is not an
+    /// integer.
+    #[error("ERR - mismatch: datatype mismatch")]
+    Mismatch,
+
     #[error("ERR - other: {0}")]
     Other(#[from] anyhow::Error),
 }
 
-impl<T> From<PoisonError<MutexGuard<'_, T>>> for Error {
-    fn from(value: PoisonError<MutexGuard<'_, T>>) -> Self {
-        err!("{value}")
+impl Error {
+    pub fn corrupt(detail: impl Into<String>) -> Self {
+        Self::Corrupt {
+            page: None,
+            detail: detail.into(),
+        }
     }
+
+    /// The SQLite result code for the error.
+    pub fn code(&self) -> ResultCode {
+        match self {
+            Self::Io(_) => ResultCode::IoErr,
+            Self::Corrupt { .. } => ResultCode::Corrupt,
+            Self::Constraint(_) => ResultCode::Constraint,
+            Self::Full => ResultCode::Full,
+            Self::Mismatch => ResultCode::Mismatch,
+            _ => ResultCode::Error,
+        }
+    }
+
+    /// Tells on which page a corruption was found, unless it is already known.
+    pub(crate) fn on_page(self, num: PageNum) -> Self {
+        match self {
+            Self::Corrupt { page: None, detail } => Self::Corrupt {
+                page: Some(num),
+                detail,
+            },
+            err => err,
+        }
+    }
+
+    /// A syntax error in `sql`, located by the input left where a parser
+    /// failed.
+    pub(crate) fn parse(sql: &str, err: nom::Err<nom::error::Error<&str>>) -> Self {
+        let (remaining, kind) = match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => (e.input, e.code),
+            nom::Err::Incomplete(_) => ("", ErrorKind::Eof),
+        };
+        let expected = match kind {
+            ErrorKind::Tag | ErrorKind::Char => "a keyword or symbol".to_string(),
+            ErrorKind::TakeWhile1 | ErrorKind::Not => "an identifier".to_string(),
+            ErrorKind::Float | ErrorKind::Digit => "a number".to_string(),
+            ErrorKind::MultiSpace => "a space".to_string(),
+            ErrorKind::Eof => "end of statement".to_string(),
+            kind => kind.description().to_lowercase(),
+        };
+
+        Self::Parse {
+            position: sql.len() - remaining.len(),
+            expected,
+        }
+    }
+}
+
+/// Primary result codes of SQLite, telling errors in a statement apart from
+/// errors reading the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultCode {
+    /// `SQLITE_ERROR`, a generic error such as invalid SQL or a missing table.
+    Error = 1,
+    /// `SQLITE_IOERR`
+    IoErr = 10,
+    /// `SQLITE_CORRUPT`
+    Corrupt = 11,
+    /// `SQLITE_FULL`
+    Full = 13,
+    /// `SQLITE_CONSTRAINT`
+    Constraint = 19,
+    /// `SQLITE_MISMATCH`
+    Mismatch = 20,
+}
+
+impl ResultCode {
+    pub fn value(&self) -> i32 {
+        *self as i32
+    }
+}
+
+fn on_page(page: &Option<PageNum>) -> String {
+    page.map(|num| format!(" on page {num}"))
+        .unwrap_or_default()
 }
 
-impl<'a> From<nom::error::Error<&'a str>> for Error {
-    fn from(value: nom::error::Error<&'a str>) -> Self {
-        err!("{value:#?}")
+impl<T> From<PoisonError<MutexGuard<'_, T>>> for Error {
+    fn from(value: PoisonError<MutexGuard<'_, T>>) -> Self {
+        err!("{value}")
     }
 }
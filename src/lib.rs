@@ -3,10 +3,12 @@ pub mod db;
 mod error;
 #[macro_use]
 mod macros;
+mod shell;
 mod sql;
 mod utils;
 
 pub type Result<T> = std::result::Result<T, Error>;
 pub use cli::Cli;
-pub use error::Error;
+pub use error::{Error, ResultCode};
+pub use shell::Shell;
 pub use sql::Sql;
@@ -1,5 +1,6 @@
 use clap::Parser;
-use codecrafters_sqlite::{db::DbFile, err, Cli, Result, Sql};
+use codecrafters_sqlite::{db::DbFile, Cli, Result, Shell};
+use std::io::{self, IsTerminal};
 
 fn main() {
     if let Err(err) = run() {
@@ -11,30 +12,14 @@ fn main() {
 fn run() -> Result<()> {
     let Cli { db_path, command } = Cli::parse();
     let db = DbFile::from_path(db_path)?;
+    let mut stdout = io::stdout().lock();
 
-    match command.as_str() {
-        ".dbinfo" => {
-            let page_size = db.file_header()?.page_size();
-            let num_tables = db.num_tables()?;
-
-            println!("database page size: {}", page_size);
-            println!("number of tables: {}", num_tables);
-        }
-        ".tables" => {
-            let tables = db.table_names()?.join(" ");
-            println!("{tables}");
-        }
-        cmd if cmd.to_lowercase().starts_with("select") => {
-            let sql = Sql::new(cmd)?;
-
-            for line in sql.execute(&db)? {
-                println!("{line}");
-            }
-        }
-        _ => {
-            return Err(err!("Unknown command: {command}"));
+    match command {
+        Some(command) => Shell::new(db).execute(&command, &mut stdout),
+        None => {
+            let stdin = io::stdin();
+            let shell = Shell::new(db).prompt(stdin.is_terminal());
+            shell.run(stdin.lock(), &mut stdout)
         }
     }
-
-    Ok(())
 }
@@ -0,0 +1,342 @@
+use super::{db::Db, Error, Result, Sql};
+use std::io::{BufRead, Read, Seek, Write};
+
+const PROMPT: &str = "sqlite> ";
+const CONTINUATION_PROMPT: &str = "   ...> ";
+
+/// Runs dot-commands and SQL statements against one database, either a single
+/// command at a time or read from an input like the `sqlite3` shell does.
+#[derive(Debug)]
+pub struct Shell<R: Read + Write + Seek> {
+    db: Db<R>,
+    prompt: bool,
+}
+
+impl<R: Read + Write + Seek> Shell<R> {
+    pub fn new(db: Db<R>) -> Self {
+        Self { db, prompt: false }
+    }
+
+    /// Whether to print prompts while reading, which only makes sense when
+    /// the input is a terminal.
+    pub fn prompt(self, prompt: bool) -> Self {
+        Self { prompt, ..self }
+    }
+
+    /// Runs a dot-command or a single SQL statement.
+    pub fn execute<W: Write>(&self, command: &str, out: &mut W) -> Result<()> {
+        match command {
+            ".dbinfo" => {
+                for (name, value) in self.dbinfo()? {
+                    writeln!(out, "{name:<20} {value}")?;
+                }
+            }
+            ".tables" => {
+                let tables = self.db.table_names()?.join(" ");
+                writeln!(out, "{tables}")?;
+            }
+            cmd if cmd.starts_with('.') => {
+                return Err(Error::Unsupported(format!("unknown command: {command}")));
+            }
+            cmd => {
+                let sql = Sql::new(cmd)?;
+
+                for line in sql.execute(&self.db)? {
+                    writeln!(out, "{line}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads commands until the input ends or `.exit`/`.quit` is given.
+    ///
+    /// Dot-commands take a single line, while SQL statements may span several
+    /// lines and run once terminated by `;`. An error is reported and the
+    /// next command is read.
+    pub fn run<I: BufRead, W: Write>(&self, mut input: I, out: &mut W) -> Result<()> {
+        let mut buffer = String::new();
+
+        loop {
+            if self.prompt {
+                let prompt = if buffer.trim().is_empty() {
+                    PROMPT
+                } else {
+                    CONTINUATION_PROMPT
+                };
+                write!(out, "{prompt}")?;
+                out.flush()?;
+            }
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            if buffer.trim().is_empty() && line.trim_start().starts_with('.') {
+                match line.trim() {
+                    ".exit" | ".quit" => return Ok(()),
+                    cmd => self.report(self.execute(cmd, out)),
+                }
+                continue;
+            }
+
+            buffer.push_str(&line);
+            let (statements, rest) = split_statements(&buffer);
+            for statement in statements {
+                self.report(self.execute(statement, out));
+            }
+            buffer = rest.to_string();
+        }
+
+        // Like sqlite3, a statement left without `;` still runs at the end.
+        if !buffer.trim().is_empty() {
+            self.report(self.execute(buffer.trim(), out));
+        }
+        if self.prompt {
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+
+    /// The fields of the file header and a summary of the schema, in the order
+    /// the `sqlite3` shell reports them.
+    fn dbinfo(&self) -> Result<Vec<(&'static str, String)>> {
+        let header = self.db.file_header()?;
+        let encoding = header.text_encoding()?;
+
+        Ok(vec![
+            ("database page size:", header.page_size().to_string()),
+            ("write format:", header.write_version().to_string()),
+            ("read format:", header.read_version().to_string()),
+            ("reserved bytes:", header.reserved_space().to_string()),
+            ("file change counter:", header.change_counter().to_string()),
+            ("database page count:", header.page_count().to_string()),
+            (
+                "freelist page count:",
+                header.freelist_page_count().to_string(),
+            ),
+            ("schema cookie:", header.schema_cookie().to_string()),
+            ("schema format:", header.schema_format().to_string()),
+            (
+                "default cache size:",
+                header.default_cache_size().to_string(),
+            ),
+            (
+                "autovacuum top root:",
+                header.auto_vacuum_root().to_string(),
+            ),
+            (
+                "incremental vacuum:",
+                header.incremental_vacuum().to_string(),
+            ),
+            (
+                "text encoding:",
+                format!("{} ({encoding})", encoding.value()),
+            ),
+            ("user version:", header.user_version().to_string()),
+            ("application id:", header.application_id().to_string()),
+            (
+                "software version:",
+                header.sqlite_version_number().to_string(),
+            ),
+            (
+                "number of tables:",
+                self.db.num_objects("table")?.to_string(),
+            ),
+            (
+                "number of indexes:",
+                self.db.num_objects("index")?.to_string(),
+            ),
+            (
+                "number of triggers:",
+                self.db.num_objects("trigger")?.to_string(),
+            ),
+            ("number of views:", self.db.num_objects("view")?.to_string()),
+            ("schema size:", self.db.schema_size()?.to_string()),
+            // What `PRAGMA data_version` reports on a fresh connection.
+            ("data version", 1.to_string()),
+        ])
+    }
+
+    fn report(&self, result: Result<()>) {
+        if let Err(err) = result {
+            eprintln!("Error: {err}");
+        }
+    }
+}
+
+/// Splits the complete statements, each terminated by a `;` outside of quotes,
+/// from the input that still waits for its terminator.
+fn split_statements(input: &str) -> (Vec<&str>, &str) {
+    let mut statements = vec![];
+    let mut start = 0;
+    let mut quote: Option<char> = None;
+
+    for (i, c) in input.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '\'' | '"' | '`' => quote = Some(c),
+                '[' => quote = Some(']'),
+                ';' => {
+                    let statement = input[start..i].trim();
+                    if !statement.is_empty() {
+                        statements.push(statement);
+                    }
+                    start = i + 1;
+                }
+                _ => {}
+            },
+        }
+    }
+
+    (statements, &input[start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db::DbFile, ResultCode};
+
+    #[test]
+    fn it_splits_statements_at_semicolons() {
+        let (statements, rest) = split_statements("select 1; select ';' from t;\nselect");
+        assert_eq!(statements, vec!["select 1", "select ';' from t"]);
+        assert_eq!(rest, "\nselect");
+
+        let (statements, rest) = split_statements("select [a;b] from t");
+        assert!(statements.is_empty());
+        assert_eq!(rest, "select [a;b] from t");
+    }
+
+    #[test]
+    fn it_runs_commands_from_input() -> Result<()> {
+        let shell = Shell::new(DbFile::from_path("sample.db")?);
+        let input = "select count(*)\nfrom apples\n;\n.tables\nselect name from apples where id = 1;\n.exit\nselect name from apples;";
+        let mut out = vec![];
+        shell.run(input.as_bytes(), &mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?,
+            "4\napples sqlite_sequence oranges\nGranny Smith\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_reports_dbinfo() -> Result<()> {
+        let shell = Shell::new(DbFile::from_path("sample.db")?);
+        let mut out = vec![];
+        shell.execute(".dbinfo", &mut out)?;
+
+        let expected = [
+            "database page size:  4096",
+            "write format:        1",
+            "read format:         1",
+            "reserved bytes:      0",
+            "file change counter: 5",
+            "database page count: 4",
+            "freelist page count: 0",
+            "schema cookie:       2",
+            "schema format:       4",
+            "default cache size:  0",
+            "autovacuum top root: 0",
+            "incremental vacuum:  0",
+            "text encoding:       1 (utf8)",
+            "user version:        0",
+            "application id:      0",
+            "software version:    3034000",
+            "number of tables:    3",
+            "number of indexes:   0",
+            "number of triggers:  0",
+            "number of views:     0",
+            "schema size:         217",
+            "data version         1",
+        ];
+        assert_eq!(
+            String::from_utf8(out)?.lines().collect::<Vec<_>>(),
+            expected
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_tells_errors_apart() -> Result<()> {
+        let bytes = std::fs::read("sample.db")?;
+        let shell = Shell::new(Db::new(std::io::Cursor::new(bytes)));
+        let error = |command| shell.execute(command, &mut vec![]).unwrap_err();
+
+        let err = error("select name from pears");
+        assert!(matches!(&err, Error::NoSuchTable(name) if name == "pears"));
+        assert_eq!(err.code(), ResultCode::Error);
+
+        let err = error("select colour from apples");
+        assert!(matches!(&err, Error::NoSuchColumn(name) if name == "colour"));
+
+        let err = error("insert into apples (id, name) values (1, 'Fuji')");
+        assert!(matches!(err, Error::Constraint(_)));
+        assert_eq!(err.code(), ResultCode::Constraint);
+
+        let err = error("insert into apples (id, name) values ('one', 'Fuji')");
+        assert!(matches!(err, Error::Mismatch));
+        assert_eq!(err.code(), ResultCode::Mismatch);
+
+        shell.execute(
+            "insert into apples (id, name) values (9223372036854775807, 'Last')",
+            &mut vec![],
+        )?;
+        let err = error("insert into apples (name) values ('Fuji')");
+        assert!(matches!(err, Error::Full));
+        assert_eq!(err.code(), ResultCode::Full);
+
+        assert!(matches!(error(".schema"), Error::Unsupported(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn it_inserts_rows() -> Result<()> {
+        let bytes = std::fs::read("sample.db")?;
+        let shell = Shell::new(Db::new(std::io::Cursor::new(bytes)));
+        let counter = shell.db.file_header()?.change_counter();
+
+        let input = "insert into oranges (name) values ('Mandarin'), ('Yuzu');\n\
+            insert into oranges values (20, 'Kumquat', 'small');\n\
+            insert into Oranges (Description, NAME) values ('sour', 'Pomelo');\n\
+            select id, name, description from oranges where id > 6;";
+        let mut out = vec![];
+        shell.run(input.as_bytes(), &mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?,
+            "7|Mandarin|\n8|Yuzu|\n20|Kumquat|small\n21|Pomelo|sour\n"
+        );
+        assert_eq!(shell.db.file_header()?.change_counter(), counter + 3);
+        assert!(shell
+            .execute("insert into oranges values (7, 'Dup', '')", &mut vec![])
+            .is_err());
+
+        // A failed insert leaves the file as it was, even after earlier rows
+        // needed overflow pages and a split.
+        let path = std::env::temp_dir().join(format!("failed-insert-{}.db", std::process::id()));
+        std::fs::copy("sample.db", &path)?;
+        let shell = Shell::new(DbFile::from_path(&path)?);
+        let long = "x".repeat(3000);
+        let longer = "y".repeat(9000);
+        let insert = format!(
+            "insert into oranges values (30, 'Long', '{long}'), (31, 'Longer', '{longer}'), (1, 'Dup', '')"
+        );
+        assert!(shell.execute(&insert, &mut vec![]).is_err());
+        let mut out = vec![];
+        shell.execute("select count(*) from oranges", &mut out)?;
+        assert_eq!(String::from_utf8(out)?, "6\n");
+
+        let bytes = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(bytes, std::fs::read("sample.db")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,519 @@
+use super::{
+    expr::{Expr, Row},
+    select::ResultSet,
+    Affinity, RecordValue, Result, Select,
+};
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    rc::Rc,
+};
+
+/// Values compared in sort order, so that rows can be grouped and deduplicated
+/// the way SQLite does, e.g. all NULLs fall into the same group.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Key(pub Vec<RecordValue>);
+
+/// Rows of a query split into groups by the `GROUP BY` expressions, with the
+/// aggregate function calls of the query computed for each group.
+#[derive(Debug)]
+pub struct Groups<'e, T: Row> {
+    group_by: &'e [Expr],
+    aggregates: Vec<&'e Expr>,
+    groups: BTreeMap<Key, Group<T>>,
+}
+
+#[derive(Debug)]
+struct Group<T: Row> {
+    row: Option<T>,
+    accumulators: Vec<Accumulator>,
+}
+
+impl<'e, T: Row> Groups<'e, T> {
+    pub fn new(group_by: &'e [Expr], aggregates: Vec<&'e Expr>) -> Result<Self> {
+        let mut unique: Vec<&Expr> = vec![];
+        for expr in aggregates {
+            if !unique.contains(&expr) {
+                Accumulator::new(expr)?;
+                unique.push(expr);
+            }
+        }
+
+        Ok(Self {
+            group_by,
+            aggregates: unique,
+            groups: BTreeMap::new(),
+        })
+    }
+
+    pub fn add(&mut self, row: T) -> Result<()> {
+        let key = self
+            .group_by
+            .iter()
+            .map(|expr| expr.eval(&row))
+            .collect::<Result<Vec<RecordValue>>>()?;
+
+        let lone_extreme = self.has_lone_extreme();
+        let group = match self.groups.entry(Key(key)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Group::new(&self.aggregates)?),
+        };
+
+        let mut changed = false;
+        for (acc, expr) in group.accumulators.iter_mut().zip(&self.aggregates) {
+            let args = match expr {
+                Expr::Function { args, .. } => args
+                    .iter()
+                    .map(|arg| arg.eval(&row))
+                    .collect::<Result<Vec<RecordValue>>>()?,
+                _ => vec![],
+            };
+            changed |= acc.update(&args)?;
+        }
+
+        // Bare columns take their values from the last row of the group, or
+        // from the row holding the value of a lone min() or max().
+        if !lone_extreme || changed || group.row.is_none() {
+            group.row = Some(row);
+        }
+
+        Ok(())
+    }
+
+    /// One row per group in the order of the group keys. An aggregate query
+    /// without `GROUP BY` always has a row, even when no row was added, which
+    /// takes its columns from `empty`.
+    pub fn into_rows(mut self, empty: T) -> Result<Vec<GroupRow<'e, T>>> {
+        if self.groups.is_empty() && self.group_by.is_empty() {
+            let mut group = Group::new(&self.aggregates)?;
+            group.row = Some(empty);
+            self.groups.insert(Key(vec![]), group);
+        }
+
+        Ok(self
+            .groups
+            .into_values()
+            .map(|group| GroupRow {
+                row: group.row,
+                aggregates: self
+                    .aggregates
+                    .iter()
+                    .zip(group.accumulators)
+                    .map(|(&expr, acc)| (expr, acc.result()))
+                    .collect(),
+            })
+            .collect())
+    }
+
+    fn has_lone_extreme(&self) -> bool {
+        matches!(
+            self.aggregates.as_slice(),
+            [Expr::Function { name, .. }] if name == "min" || name == "max"
+        )
+    }
+}
+
+impl<T: Row> Group<T> {
+    fn new(aggregates: &[&Expr]) -> Result<Self> {
+        Ok(Self {
+            row: None,
+            accumulators: aggregates
+                .iter()
+                .map(|expr| Accumulator::new(expr))
+                .collect::<Result<Vec<Accumulator>>>()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct GroupRow<'e, T: Row> {
+    row: Option<T>,
+    aggregates: Vec<(&'e Expr, RecordValue)>,
+}
+
+impl<T: Row> Row for GroupRow<'_, T> {
+    fn col(&self, table: Option<&str>, name: &str) -> Result<RecordValue> {
+        match &self.row {
+            Some(row) => row.col(table, name),
+            None => Ok(RecordValue::Null),
+        }
+    }
+
+    fn affinity(&self, table: Option<&str>, name: &str) -> Affinity {
+        match &self.row {
+            Some(row) => row.affinity(table, name),
+            None => Affinity::Blob,
+        }
+    }
+
+    fn aggregate(&self, expr: &Expr) -> Result<RecordValue> {
+        self.aggregates
+            .iter()
+            .find(|(e, _)| *e == expr)
+            .map(|(_, value)| value.clone())
+            .ok_or(err!("Aggregate function was not computed"))
+    }
+
+    fn query(&self, select: &Select) -> Result<Rc<ResultSet>> {
+        match &self.row {
+            Some(row) => row.query(select),
+            None => Err(err!("Group has no row to run a subquery for")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Count,
+    Sum,
+    Total,
+    Avg,
+    Min,
+    Max,
+    GroupConcat,
+}
+
+#[derive(Debug)]
+enum State {
+    Count(i64),
+    /// Running sum for `sum`, `total` and `avg`, where the integer sum is
+    /// dropped as soon as a non integer value is added.
+    Sum {
+        int: Option<i64>,
+        float: f64,
+        count: i64,
+    },
+    Extreme(Option<RecordValue>),
+    Concat(Option<String>),
+}
+
+#[derive(Debug)]
+struct Accumulator {
+    function: Function,
+    distinct: Option<BTreeSet<Key>>,
+    state: State,
+}
+
+impl Accumulator {
+    fn new(expr: &Expr) -> Result<Self> {
+        let Expr::Function {
+            name,
+            args,
+            distinct,
+        } = expr
+        else {
+            return Err(err!("Not an aggregate function call"));
+        };
+
+        let (function, state) = match name.as_str() {
+            "count" => (Function::Count, State::Count(0)),
+            "sum" | "total" | "avg" => {
+                let function = match name.as_str() {
+                    "sum" => Function::Sum,
+                    "total" => Function::Total,
+                    _ => Function::Avg,
+                };
+                let state = State::Sum {
+                    int: Some(0),
+                    float: 0.0,
+                    count: 0,
+                };
+                (function, state)
+            }
+            "min" => (Function::Min, State::Extreme(None)),
+            "max" => (Function::Max, State::Extreme(None)),
+            "group_concat" => (Function::GroupConcat, State::Concat(None)),
+            _ => return Err(err!("no such aggregate function: {name}")),
+        };
+
+        let arity_ok = match function {
+            Function::Count => args.len() <= 1 && !(*distinct && args.is_empty()),
+            Function::GroupConcat => (1..=2).contains(&args.len()),
+            _ => args.len() == 1,
+        };
+        if !arity_ok {
+            return Err(err!("wrong number of arguments to function {name}()"));
+        }
+
+        Ok(Self {
+            function,
+            distinct: distinct.then(BTreeSet::new),
+            state,
+        })
+    }
+
+    /// Adds the argument values of one row and tells whether the result
+    /// changed.
+    fn update(&mut self, args: &[RecordValue]) -> Result<bool> {
+        let Some(value) = args.first() else {
+            // count(*) counts every row.
+            if let State::Count(n) = &mut self.state {
+                *n += 1;
+            }
+            return Ok(true);
+        };
+
+        if matches!(value, RecordValue::Null) {
+            return Ok(false);
+        }
+        if let Some(seen) = &mut self.distinct {
+            if !seen.insert(Key(vec![value.clone()])) {
+                return Ok(false);
+            }
+        }
+
+        match &mut self.state {
+            State::Count(n) => *n += 1,
+            State::Sum { int, float, count } => {
+                let (as_int, as_float) = numeric(value);
+                *count += 1;
+                *float += as_float;
+                *int = match (*int, as_int) {
+                    (Some(a), Some(b)) => match a.checked_add(b) {
+                        Some(sum) => Some(sum),
+                        None if self.function == Function::Sum => {
+                            return Err(err!("integer overflow"))
+                        }
+                        None => None,
+                    },
+                    _ => None,
+                };
+            }
+            State::Extreme(extreme) => {
+                let replace = extreme.as_ref().map_or(true, |current| {
+                    let order = value.cmp(current);
+                    match self.function {
+                        Function::Min => order.is_lt(),
+                        _ => order.is_gt(),
+                    }
+                });
+                if replace {
+                    *extreme = Some(value.clone());
+                }
+                return Ok(replace);
+            }
+            State::Concat(text) => match text {
+                Some(text) => {
+                    let separator = match args.get(1) {
+                        Some(RecordValue::Null) => String::new(),
+                        Some(sep) => sep.to_string(),
+                        None => ",".into(),
+                    };
+                    text.push_str(&separator);
+                    text.push_str(&value.to_string());
+                }
+                None => *text = Some(value.to_string()),
+            },
+        }
+
+        Ok(true)
+    }
+
+    fn result(self) -> RecordValue {
+        match self.state {
+            State::Count(n) => RecordValue::Int(n),
+            State::Sum { int, float, count } => match self.function {
+                Function::Total => RecordValue::Float(float),
+                _ if count == 0 => RecordValue::Null,
+                Function::Avg => RecordValue::Float(float / count as f64),
+                _ => int.map_or(RecordValue::Float(float), RecordValue::Int),
+            },
+            State::Extreme(value) => value.unwrap_or(RecordValue::Null),
+            State::Concat(text) => text.map_or(RecordValue::Null, RecordValue::Text),
+        }
+    }
+}
+
+/// A value as an integer if it is one, and as a float. Text is summed as an
+/// integer when it reads as one and as a float otherwise, like SQLite does.
+fn numeric(value: &RecordValue) -> (Option<i64>, f64) {
+    match value {
+        RecordValue::PrimaryKey(n) => (Some(*n as i64), *n as f64),
+        RecordValue::Int(n) => (Some(*n), *n as f64),
+        RecordValue::Float(n) => (None, *n),
+        RecordValue::Text(t) => match t.trim().parse::<i64>() {
+            Ok(n) => (Some(n), n as f64),
+            Err(_) => (None, t.trim().parse().unwrap_or(0.0)),
+        },
+        RecordValue::Null | RecordValue::Blob(_) => (None, 0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Value(RecordValue);
+
+    impl Row for Value {
+        fn col(&self, _table: Option<&str>, _name: &str) -> Result<RecordValue> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn call(name: &str, distinct: bool) -> Expr {
+        Expr::Function {
+            name: name.into(),
+            args: vec![Expr::column("x")],
+            distinct,
+        }
+    }
+
+    fn aggregate(expr: &Expr, values: Vec<RecordValue>) -> RecordValue {
+        let mut groups = Groups::new(&[], vec![expr]).unwrap();
+        for value in values {
+            groups.add(Value(value)).unwrap();
+        }
+        let rows = groups.into_rows(Value(RecordValue::Null)).unwrap();
+        rows[0].aggregate(expr).unwrap()
+    }
+
+    fn ints(values: &[i64]) -> Vec<RecordValue> {
+        values.iter().map(|n| RecordValue::Int(*n)).collect()
+    }
+
+    #[test]
+    fn it_counts_rows_and_values() {
+        let count_all = Expr::Function {
+            name: "count".into(),
+            args: vec![],
+            distinct: false,
+        };
+        let values = vec![RecordValue::Int(1), RecordValue::Null, RecordValue::Int(1)];
+        assert_eq!(aggregate(&count_all, values.clone()).to_string(), "3");
+        assert_eq!(
+            aggregate(&call("count", false), values.clone()).to_string(),
+            "2"
+        );
+        assert_eq!(aggregate(&call("count", true), values).to_string(), "1");
+    }
+
+    #[test]
+    fn it_sums_values() {
+        assert_eq!(
+            aggregate(&call("sum", false), ints(&[1, 2, 3])).to_string(),
+            "6"
+        );
+        assert_eq!(
+            aggregate(&call("sum", true), ints(&[1, 2, 2])).to_string(),
+            "3"
+        );
+        assert_eq!(
+            aggregate(&call("total", false), ints(&[1, 2])).to_string(),
+            "3.0"
+        );
+        assert_eq!(
+            aggregate(&call("avg", false), ints(&[1, 2])).to_string(),
+            "1.5"
+        );
+
+        let mixed = vec![RecordValue::Int(1), RecordValue::Float(0.5)];
+        assert_eq!(aggregate(&call("sum", false), mixed).to_string(), "1.5");
+
+        let text = |values: &[&str]| {
+            values
+                .iter()
+                .map(|t| RecordValue::Text(t.to_string()))
+                .collect()
+        };
+        assert_eq!(
+            aggregate(&call("sum", false), text(&["3", " 4 "])).to_string(),
+            "7"
+        );
+        assert_eq!(
+            aggregate(&call("sum", false), text(&["3", "2.5"])).to_string(),
+            "5.5"
+        );
+        assert_eq!(
+            aggregate(&call("sum", false), text(&["abc"])).to_string(),
+            "0.0"
+        );
+
+        assert!(matches!(
+            aggregate(&call("sum", false), vec![]),
+            RecordValue::Null
+        ));
+        assert!(matches!(
+            aggregate(&call("avg", false), vec![RecordValue::Null]),
+            RecordValue::Null
+        ));
+        assert_eq!(aggregate(&call("total", false), vec![]).to_string(), "0.0");
+    }
+
+    #[test]
+    fn it_fails_on_integer_overflow() {
+        let expr = call("sum", false);
+        let mut groups = Groups::new(&[], vec![&expr]).unwrap();
+        groups.add(Value(RecordValue::Int(i64::MAX))).unwrap();
+        assert!(groups.add(Value(RecordValue::Int(1))).is_err());
+    }
+
+    #[test]
+    fn it_finds_extremes() {
+        let values = vec![
+            RecordValue::Text("a".into()),
+            RecordValue::Int(3),
+            RecordValue::Null,
+            RecordValue::Float(0.5),
+        ];
+        assert_eq!(
+            aggregate(&call("min", false), values.clone()).to_string(),
+            "0.5"
+        );
+        assert_eq!(aggregate(&call("max", false), values).to_string(), "a");
+    }
+
+    #[test]
+    fn it_concatenates_values() {
+        let values = vec![
+            RecordValue::Text("a".into()),
+            RecordValue::Null,
+            RecordValue::Text("b".into()),
+            RecordValue::Text("a".into()),
+        ];
+        assert_eq!(
+            aggregate(&call("group_concat", false), values.clone()).to_string(),
+            "a,b,a"
+        );
+        assert_eq!(
+            aggregate(&call("group_concat", true), values.clone()).to_string(),
+            "a,b"
+        );
+
+        let with_separator = Expr::Function {
+            name: "group_concat".into(),
+            args: vec![
+                Expr::column("x"),
+                Expr::Literal(super::super::expr::Literal::Text("-".into())),
+            ],
+            distinct: false,
+        };
+        assert_eq!(aggregate(&with_separator, values).to_string(), "a-b-a");
+    }
+
+    #[test]
+    fn it_groups_rows_by_key() {
+        let count_all = Expr::Function {
+            name: "count".into(),
+            args: vec![],
+            distinct: false,
+        };
+        let group_by = [Expr::column("x")];
+        let mut groups = Groups::new(&group_by, vec![&count_all]).unwrap();
+        for n in [2, 1, 2, 2] {
+            groups.add(Value(RecordValue::Int(n))).unwrap();
+        }
+
+        let rows = groups.into_rows(Value(RecordValue::Null)).unwrap();
+        let counts: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "{}:{}",
+                    row.col(None, "x").unwrap(),
+                    row.aggregate(&count_all).unwrap()
+                )
+            })
+            .collect();
+        assert_eq!(counts, vec!["1:1", "2:3"]);
+    }
+}
@@ -0,0 +1,187 @@
+use super::{expr::Expr, Affinity, Select};
+
+/// `CREATE TABLE name (columns[, constraints]) [options]`, as stored in the
+/// schema table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDefinition {
+    pub name: String,
+    pub columns: Vec<ColumnDefinition>,
+    pub constraints: Vec<TableConstraint>,
+    pub without_rowid: bool,
+    pub strict: bool,
+}
+
+impl TableDefinition {
+    /// The primary key columns, declared either on a column or as a table
+    /// constraint, with the collations they are compared by.
+    pub fn primary_key(&self) -> Vec<IndexedColumn> {
+        let table_key = self.constraints.iter().find_map(|c| match c {
+            TableConstraint::PrimaryKey(cols) => Some(cols.clone()),
+            _ => None,
+        });
+
+        let key = table_key.unwrap_or_else(|| {
+            self.columns
+                .iter()
+                .filter_map(|col| {
+                    col.constraints.iter().find_map(|c| match c {
+                        ColumnConstraint::PrimaryKey { desc, .. } => Some(IndexedColumn {
+                            name: col.name.clone(),
+                            collation: None,
+                            desc: *desc,
+                        }),
+                        _ => None,
+                    })
+                })
+                .collect()
+        });
+        self.with_collations(key)
+    }
+
+    /// The columns of a key, where a column without a collation of its own
+    /// uses the one declared on the table column.
+    pub fn with_collations(&self, mut key: Vec<IndexedColumn>) -> Vec<IndexedColumn> {
+        for key_col in &mut key {
+            if key_col.collation.is_none() {
+                key_col.collation = self.position(&key_col.name).and_then(|idx| {
+                    self.columns[idx].constraints.iter().find_map(|c| match c {
+                        ColumnConstraint::Collate(name) => Some(name.clone()),
+                        _ => None,
+                    })
+                });
+            }
+        }
+        key
+    }
+
+    /// Position of the column whose values are the rowids, which is the only
+    /// primary key column when its declared type is exactly `INTEGER`, in any
+    /// case.
+    ///
+    /// Like in SQLite, `INTEGER PRIMARY KEY DESC` declared on the column makes
+    /// an ordinary primary key, while `PRIMARY KEY (col DESC)` on the table
+    /// still makes an alias.
+    pub fn rowid_alias(&self) -> Option<usize> {
+        if self.without_rowid {
+            return None;
+        }
+        let [key] = &self.primary_key()[..] else {
+            return None;
+        };
+        let idx = self.position(&key.name)?;
+        let col = &self.columns[idx];
+        let is_desc_column_key = col
+            .constraints
+            .iter()
+            .any(|c| matches!(c, ColumnConstraint::PrimaryKey { desc: true, .. }));
+
+        (col.r#type.eq_ignore_ascii_case("integer") && !is_desc_column_key).then_some(idx)
+    }
+
+    /// Positions in records of the columns in declared order. The records of
+    /// a `WITHOUT ROWID` table start with the primary key columns, followed
+    /// by the others.
+    pub fn record_positions(&self) -> Vec<usize> {
+        if !self.without_rowid {
+            return (0..self.columns.len()).collect();
+        }
+
+        let key = self.primary_key();
+        let key_positions = key.iter().filter_map(|col| self.position(&col.name));
+        let mut order: Vec<usize> = vec![];
+        for idx in key_positions.chain(0..self.columns.len()) {
+            if !order.contains(&idx) {
+                order.push(idx);
+            }
+        }
+
+        let mut positions = vec![0; self.columns.len()];
+        for (position, idx) in order.into_iter().enumerate() {
+            positions[idx] = position;
+        }
+        positions
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|col| col.name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// `CREATE VIEW name [(columns)] AS select`, as stored in the schema table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewDefinition {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub select: Select,
+}
+
+/// `name [type] [constraints]`, where the type is kept as written and is
+/// empty when none is declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub r#type: String,
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+impl ColumnDefinition {
+    pub fn affinity(&self) -> Affinity {
+        Affinity::new(&self.r#type)
+    }
+}
+
+/// A constraint following a column's type. Constraint names and conflict
+/// clauses are not kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnConstraint {
+    PrimaryKey { desc: bool, autoincrement: bool },
+    NotNull,
+    Unique,
+    Check(Expr),
+    Default(Expr),
+    Collate(String),
+    References(ForeignKey),
+    Generated { expr: Expr, stored: bool },
+}
+
+/// A constraint on several columns, following the column definitions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<IndexedColumn>),
+    Unique(Vec<IndexedColumn>),
+    Check(Expr),
+    ForeignKey {
+        columns: Vec<String>,
+        references: ForeignKey,
+    },
+}
+
+/// `name [COLLATE collation] [ASC | DESC]` in the key of an index, a primary
+/// key or a unique constraint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedColumn {
+    pub name: String,
+    pub collation: Option<String>,
+    pub desc: bool,
+}
+
+impl IndexedColumn {
+    /// Whether keys are in ascending order of their values, which holds with
+    /// the default `BINARY` collation.
+    pub fn is_ascending_binary(&self) -> bool {
+        !self.desc
+            && self
+                .collation
+                .as_ref()
+                .map_or(true, |name| name.eq_ignore_ascii_case("binary"))
+    }
+}
+
+/// `REFERENCES table [(columns)]`, without the actions taken on changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignKey {
+    pub table: String,
+    pub columns: Vec<String>,
+}
@@ -0,0 +1,301 @@
+use super::{
+    aggregate::Key,
+    join::Outer,
+    select::{Compound, CompoundOp, Limit, ResultSet, TableOrSubquery},
+    Db, Error, Result, Select,
+};
+use std::{
+    collections::BTreeSet,
+    io::{Read, Seek},
+    rc::Rc,
+};
+
+/// `name [(columns)] AS (select)` in a `WITH` clause.
+///
+/// Like in SQLite, the table is recursive when the last select of a compound
+/// is joined by `UNION [ALL]` and reads from the table itself, whether the
+/// clause says `RECURSIVE` or not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonTableExpr {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub select: Select,
+}
+
+/// The rows of the tables named by the `WITH` clauses of a query and of the
+/// queries it is nested in, where a later table hides an earlier one with the
+/// same name, and the views the query is part of.
+#[derive(Debug, Clone, Default)]
+pub struct Ctes {
+    tables: Vec<(String, Rc<ResultSet>)>,
+    views: Vec<String>,
+}
+
+impl Ctes {
+    pub fn get(&self, name: &str) -> Option<&Rc<ResultSet>> {
+        self.tables
+            .iter()
+            .rev()
+            .find(|(cte, _)| cte.eq_ignore_ascii_case(name))
+            .map(|(_, result)| result)
+    }
+
+    /// These tables and those of a `WITH` clause, each of which runs once and
+    /// can read from the ones before it.
+    pub fn with<R: Read + Seek>(
+        &self,
+        db: &Db<R>,
+        with: &[CommonTableExpr],
+        outer: Option<Outer<'_, R>>,
+    ) -> Result<Self> {
+        let mut ctes = self.clone();
+        for (idx, cte) in with.iter().enumerate() {
+            if with[..idx]
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&cte.name))
+            {
+                return Err(err!("duplicate WITH table name: {}", cte.name));
+            }
+            let result = cte.run(db, &ctes, outer)?;
+            ctes.tables.push((cte.name.clone(), Rc::new(result)));
+        }
+        Ok(ctes)
+    }
+
+    /// What the query of a view runs with: none of the tables, as a view only
+    /// reads from the database, and the views it is part of, so that one
+    /// reading from itself is an error rather than never ending.
+    pub fn view(&self, name: &str) -> Result<Self> {
+        if self
+            .views
+            .iter()
+            .any(|view| view.eq_ignore_ascii_case(name))
+        {
+            return Err(err!("view {name} is circularly defined"));
+        }
+        let mut views = self.views.clone();
+        views.push(name.to_string());
+        Ok(Self {
+            tables: vec![],
+            views,
+        })
+    }
+}
+
+impl CommonTableExpr {
+    /// The rows of the table.
+    ///
+    /// A recursive table starts with the rows of the selects before the
+    /// recursive one, then runs the recursive select with the rows it added
+    /// last as the table, until it adds none. The `LIMIT` stops a recursion
+    /// which would never end.
+    fn run<R: Read + Seek>(
+        &self,
+        db: &Db<R>,
+        ctes: &Ctes,
+        outer: Option<Outer<'_, R>>,
+    ) -> Result<ResultSet> {
+        let step = self.recursive_step();
+        let mut result = match step {
+            None => self.select.query(db, ctes, outer)?,
+            Some(_) if !self.select.order_by.is_empty() => {
+                return Err(Error::Unsupported(format!(
+                    "ORDER BY in the recursive table {}",
+                    self.name
+                )));
+            }
+            Some(_) => {
+                let compound = &self.select.compound;
+                let initial = Select {
+                    compound: compound[..compound.len() - 1].to_vec(),
+                    limit: Limit::default(),
+                    ..self.select.clone()
+                };
+                initial.query(db, ctes, outer)?
+            }
+        };
+        if !self.columns.is_empty() {
+            if self.columns.len() != result.columns.len() {
+                return Err(err!(
+                    "table {} has {} values for {} columns",
+                    self.name,
+                    result.columns.len(),
+                    self.columns.len()
+                ));
+            }
+            for ((name, _), column) in result.columns.iter_mut().zip(&self.columns) {
+                name.clone_from(column);
+            }
+        }
+
+        let Some(step) = step else {
+            return Ok(result);
+        };
+
+        let all = step.op == CompoundOp::UnionAll;
+        let mut seen = BTreeSet::new();
+        if !all {
+            result.rows.retain(|row| seen.insert(Key(row.clone())));
+        }
+
+        let limit = self.select.limit;
+        let is_full = |rows: &[Vec<_>]| limit.count.is_some_and(|n| rows.len() >= limit.offset + n);
+
+        let mut added = result.rows.clone();
+        while !added.is_empty() && !is_full(&result.rows) {
+            let mut ctes = ctes.clone();
+            let table = ResultSet {
+                columns: result.columns.clone(),
+                rows: added,
+            };
+            ctes.tables.push((self.name.clone(), Rc::new(table)));
+
+            let rows = step.select.query(db, &ctes, outer)?;
+            if rows.columns.len() != result.columns.len() {
+                return Err(err!(
+                    "SELECTs to the left and right of {} do not have the same number of result columns",
+                    step.op.keyword()
+                ));
+            }
+            added = rows.rows;
+            if !all {
+                added.retain(|row| seen.insert(Key(row.clone())));
+            }
+            result.rows.extend(added.iter().cloned());
+        }
+
+        result.rows = limit.apply(result.rows);
+        Ok(result)
+    }
+
+    /// The select which reads from the table itself, if it is recursive.
+    fn recursive_step(&self) -> Option<&Compound> {
+        let step = self.select.compound.last()?;
+        let reads_self = step.select.from.iter().any(|item| {
+            matches!(&item.table, TableOrSubquery::Table(name) if name.eq_ignore_ascii_case(&self.name))
+        });
+        (reads_self && matches!(step.op, CompoundOp::Union | CompoundOp::UnionAll)).then_some(step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::{Db, RecordValue},
+        sql::query,
+        Result, Sql,
+    };
+
+    #[test]
+    fn it_reads_tables_of_with_clauses() -> Result<()> {
+        assert_eq!(
+            query(
+                "with red(key, apple) as (select id, name from apples where color like '%red%') \
+                 select o.name, red.apple from oranges o join red on o.id = red.key"
+            )?,
+            vec!["Tangelo|Fuji", "Tangerine|Honeycrisp"]
+        );
+        assert_eq!(
+            query(
+                "with a as (select id from apples), b as (select id from a where id > 2) \
+                 select count(*) from oranges where id in (select id from b)"
+            )?,
+            vec!["2"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_recursive_tables() -> Result<()> {
+        let recursive = |union: &str, filter: &str| {
+            query(&format!(
+                "with recursive n(id) as (select id from apples where id = 1 \
+                 {union} select o.id from oranges o, n where o.id > n.id {filter}) \
+                 select * from n"
+            ))
+        };
+        assert_eq!(
+            recursive("union all", "and o.id < 4")?,
+            vec!["1", "2", "3", "3"]
+        );
+        assert_eq!(recursive("union", "and o.id < 4")?, vec!["1", "2", "3"]);
+        assert_eq!(recursive("union all", "limit 4")?, vec!["1", "2", "3", "4"]);
+        Ok(())
+    }
+
+    #[test]
+    fn it_walks_a_tree_of_rows() -> Result<()> {
+        // Every employee but the heads of the company has a manager.
+        let managers = [
+            ("Ada", None),
+            ("Bob", Some(1)),
+            ("Cy", Some(1)),
+            ("Dee", Some(2)),
+            ("Eve", Some(4)),
+            ("Fay", Some(3)),
+            ("Gus", None),
+            ("Hal", Some(7)),
+        ];
+        let rows = managers
+            .iter()
+            .map(|(name, manager)| {
+                let manager = manager.map_or(RecordValue::Null, RecordValue::Int);
+                vec![
+                    RecordValue::Null,
+                    RecordValue::Text(name.to_string()),
+                    manager,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let db = Db::sample_with_table(
+            "emp",
+            "CREATE TABLE emp (id integer primary key, name text, manager_id integer)",
+            &rows,
+        )?;
+        let query = |sql| Sql::new(sql)?.execute(&db);
+
+        assert_eq!(
+            query(
+                "with recursive team(id, name) as (select id, name from emp where id = 1 \
+                 union all select e.id, e.name from emp e join team on e.manager_id = team.id) \
+                 select name from team order by name"
+            )?,
+            vec!["Ada", "Bob", "Cy", "Dee", "Eve", "Fay"]
+        );
+        assert_eq!(
+            query(
+                "with recursive boss(id, manager) as \
+                 (select id, manager_id from emp where name = 'Eve' union all \
+                 select e.id, e.manager_id from emp e join boss on e.id = boss.manager) \
+                 select id from boss"
+            )?,
+            vec!["5", "4", "2", "1"]
+        );
+        assert_eq!(
+            query(
+                "with recursive team(id) as (select id from emp where manager_id is null \
+                 union select e.id from emp e, team where e.manager_id = team.id) \
+                 select count(*) from team"
+            )?,
+            vec!["8"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_checks_with_clauses() {
+        let message = |sql| query(sql).unwrap_err().to_string();
+        assert!(message(
+            "with a as (select id from apples), a as (select id from apples) select * from a"
+        )
+        .contains("duplicate WITH table name: a"));
+        assert!(
+            message("with s(x, y) as (select id from apples) select * from s")
+                .contains("table s has 1 values for 2 columns")
+        );
+        assert!(message(
+            "with s as (select id from apples union select id, name from oranges) select * from s"
+        )
+        .contains("do not have the same number of result columns"));
+    }
+}
@@ -0,0 +1,749 @@
+use super::{select::ResultSet, Affinity, Error, RecordValue, Result, Select, TableRow};
+use std::cmp::Ordering;
+use std::io::{Read, Seek};
+use std::rc::Rc;
+
+/// Values a column reference can be resolved against while evaluating an
+/// expression. A column may be qualified by the name of its table.
+pub trait Row {
+    fn col(&self, table: Option<&str>, name: &str) -> Result<RecordValue>;
+
+    /// Affinity of a column, which decides how it is compared with values of
+    /// other types. Values without a column have none.
+    fn affinity(&self, _table: Option<&str>, _name: &str) -> Affinity {
+        Affinity::Blob
+    }
+
+    /// Result of an aggregate function call, which only a group of rows has.
+    fn aggregate(&self, expr: &Expr) -> Result<RecordValue> {
+        match expr {
+            Expr::Function { name, .. } => Err(err!("misuse of aggregate function {name}()")),
+            _ => Err(err!("Not an aggregate function call")),
+        }
+    }
+
+    /// Result of a subquery run for this row, which only the rows of a query
+    /// can run.
+    fn query(&self, _select: &Select) -> Result<Rc<ResultSet>> {
+        Err(Error::Unsupported(
+            "subqueries outside of a query".to_string(),
+        ))
+    }
+}
+
+/// A row of a single table, to which every column belongs.
+impl<R: Read + Seek> Row for TableRow<'_, R> {
+    fn col(&self, _table: Option<&str>, name: &str) -> Result<RecordValue> {
+        TableRow::col(self, name)
+    }
+
+    fn affinity(&self, _table: Option<&str>, name: &str) -> Affinity {
+        TableRow::affinity(self, name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl From<&Literal> for RecordValue {
+    fn from(value: &Literal) -> Self {
+        match value {
+            Literal::Null => Self::Null,
+            Literal::Integer(n) => Self::Int(*n),
+            Literal::Float(n) => Self::Float(*n),
+            Literal::Text(t) => Self::Text(t.clone()),
+        }
+    }
+}
+
+impl Literal {
+    /// The literal for a value, which a blob has none of.
+    pub fn from_value(value: &RecordValue) -> Option<Self> {
+        match value {
+            RecordValue::Null => Some(Self::Null),
+            RecordValue::Int(n) => Some(Self::Integer(*n)),
+            RecordValue::PrimaryKey(n) => i64::try_from(*n).ok().map(Self::Integer),
+            RecordValue::Float(n) => Some(Self::Float(*n)),
+            RecordValue::Text(t) => Some(Self::Text(t.clone())),
+            RecordValue::Blob(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Is,
+    IsNot,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+    Glob,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    /// A column, optionally qualified by the name of its table.
+    Column {
+        table: Option<String>,
+        name: String,
+    },
+    Not(Box<Expr>),
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        right: Box<Expr>,
+    },
+    In {
+        expr: Box<Expr>,
+        list: Vec<Expr>,
+    },
+    Between {
+        expr: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+    },
+    /// A function call, with the name in lower case and no arguments for
+    /// `count(*)`.
+    Function {
+        name: String,
+        args: Vec<Expr>,
+        distinct: bool,
+    },
+    /// The value of the first column of the first row of a query, or NULL.
+    Subquery(Box<Select>),
+    Exists(Box<Select>),
+    InSelect {
+        expr: Box<Expr>,
+        select: Box<Select>,
+    },
+}
+
+impl Expr {
+    pub fn column(name: impl Into<String>) -> Self {
+        Self::Column {
+            table: None,
+            name: name.into(),
+        }
+    }
+
+    pub fn binary(left: Expr, op: BinaryOp, right: Expr) -> Self {
+        Self::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        }
+    }
+
+    pub fn not(expr: Expr) -> Self {
+        Self::Not(Box::new(expr))
+    }
+
+    /// Evaluates the expression against a row. Boolean results follow SQLite
+    /// and are represented as the integers 1 and 0, with NULL meaning unknown.
+    pub fn eval<T: Row + ?Sized>(&self, row: &T) -> Result<RecordValue> {
+        match self {
+            Self::Literal(lit) => Ok(lit.into()),
+            Self::Column { table, name } => row.col(table.as_deref(), name),
+            Self::Not(expr) => Ok(from_bool(truth(&expr.eval(row)?).map(|b| !b))),
+            Self::Binary { left, op, right } => eval_binary(left, *op, right, row),
+            Self::In { expr, list } => {
+                let items = list
+                    .iter()
+                    .map(|item| Ok((item.eval(row)?, item.affinity(row))));
+                is_in((expr.eval(row)?, expr.affinity(row)), items)
+            }
+            Self::InSelect { expr, select } => {
+                let result = row.query(select)?;
+                let affinity = single_column(&result)?;
+                let items = result.rows.iter().map(|r| Ok((r[0].clone(), affinity)));
+                is_in((expr.eval(row)?, expr.affinity(row)), items)
+            }
+            Self::Subquery(select) => {
+                let result = row.query(select)?;
+                single_column(&result)?;
+                Ok(result
+                    .rows
+                    .first()
+                    .map_or(RecordValue::Null, |r| r[0].clone()))
+            }
+            Self::Exists(select) => Ok(from_bool(Some(!row.query(select)?.rows.is_empty()))),
+            Self::Between { expr, low, high } => {
+                let value = (expr.eval(row)?, expr.affinity(row));
+                let (v, low) = coerce(value.clone(), (low.eval(row)?, low.affinity(row)));
+                let above = compare(&v, &low).map(|o| o.is_ge());
+                let (v, high) = coerce(value, (high.eval(row)?, high.affinity(row)));
+                let below = compare(&v, &high).map(|o| o.is_le());
+                Ok(from_bool(and(above, below)))
+            }
+            Self::Function { name, .. } if is_aggregate(name) => row.aggregate(self),
+            Self::Function { name, .. } => Err(err!("no such function: {name}")),
+        }
+    }
+
+    /// Aggregate function calls in the expression, outermost first.
+    pub fn aggregates(&self) -> Vec<&Expr> {
+        let mut found = vec![];
+        let mut exprs = vec![self];
+
+        while let Some(expr) = exprs.pop() {
+            match expr {
+                Self::Function { name, .. } if is_aggregate(name) => found.push(expr),
+                expr => exprs.extend(expr.operands().into_iter().rev()),
+            }
+        }
+
+        found
+    }
+
+    /// The terms joined by `AND` at the top level of the expression.
+    pub fn conjuncts(&self) -> Vec<&Expr> {
+        match self {
+            Self::Binary {
+                left,
+                op: BinaryOp::And,
+                right,
+            } => [left.conjuncts(), right.conjuncts()].concat(),
+            expr => vec![expr],
+        }
+    }
+
+    /// The columns the expression refers to, as their table and name, from
+    /// left to right.
+    pub fn columns(&self) -> Vec<(Option<&str>, &str)> {
+        let mut found = vec![];
+        let mut exprs = vec![self];
+
+        while let Some(expr) = exprs.pop() {
+            match expr {
+                Self::Column { table, name } => found.push((table.as_deref(), name.as_str())),
+                expr => exprs.extend(expr.operands().into_iter().rev()),
+            }
+        }
+
+        found
+    }
+
+    /// The expression with the columns for which `f` gives an expression
+    /// replaced by it, or the first error `f` returns. The columns of
+    /// subqueries are left as they are.
+    pub fn map_columns(
+        &self,
+        f: &impl Fn(Option<&str>, &str) -> Result<Option<Expr>>,
+    ) -> Result<Expr> {
+        let map = |exprs: &[Expr]| {
+            exprs
+                .iter()
+                .map(|expr| expr.map_columns(f))
+                .collect::<Result<Vec<Expr>>>()
+        };
+        let boxed = |expr: &Expr| expr.map_columns(f).map(Box::new);
+
+        Ok(match self {
+            Self::Literal(_) | Self::Subquery(_) | Self::Exists(_) => self.clone(),
+            Self::Column { table, name } => {
+                f(table.as_deref(), name)?.unwrap_or_else(|| self.clone())
+            }
+            Self::Not(expr) => Self::Not(boxed(expr)?),
+            Self::Binary { left, op, right } => Self::Binary {
+                left: boxed(left)?,
+                op: *op,
+                right: boxed(right)?,
+            },
+            Self::In { expr, list } => Self::In {
+                expr: boxed(expr)?,
+                list: map(list)?,
+            },
+            Self::InSelect { expr, select } => Self::InSelect {
+                expr: boxed(expr)?,
+                select: select.clone(),
+            },
+            Self::Between { expr, low, high } => Self::Between {
+                expr: boxed(expr)?,
+                low: boxed(low)?,
+                high: boxed(high)?,
+            },
+            Self::Function {
+                name,
+                args,
+                distinct,
+            } => Self::Function {
+                name: name.clone(),
+                args: map(args)?,
+                distinct: *distinct,
+            },
+        })
+    }
+
+    /// The expressions directly inside this one, from left to right, which
+    /// leaves out those of subqueries.
+    fn operands(&self) -> Vec<&Expr> {
+        match self {
+            Self::Literal(_) | Self::Column { .. } | Self::Subquery(_) | Self::Exists(_) => vec![],
+            Self::InSelect { expr, .. } => vec![expr],
+            Self::Not(expr) => vec![expr],
+            Self::Binary { left, right, .. } => vec![left, right],
+            Self::In { expr, list } => [expr.as_ref()].into_iter().chain(list).collect(),
+            Self::Between { expr, low, high } => vec![expr, low, high],
+            Self::Function { args, .. } => args.iter().collect(),
+        }
+    }
+
+    /// Affinity of the value of the expression, which only a column has.
+    fn affinity<T: Row + ?Sized>(&self, row: &T) -> Affinity {
+        match self {
+            Self::Column { table, name } => row.affinity(table.as_deref(), name),
+            _ => Affinity::Blob,
+        }
+    }
+
+    /// Whether the expression holds for the row. Unknown counts as false.
+    pub fn is_true<T: Row + ?Sized>(&self, row: &T) -> Result<bool> {
+        self.eval(row).map(|v| truth(&v).unwrap_or(false))
+    }
+}
+
+pub fn is_aggregate(name: &str) -> bool {
+    matches!(
+        name,
+        "count" | "sum" | "total" | "avg" | "min" | "max" | "group_concat"
+    )
+}
+
+fn eval_binary<T: Row + ?Sized>(
+    left: &Expr,
+    op: BinaryOp,
+    right: &Expr,
+    row: &T,
+) -> Result<RecordValue> {
+    let l = left.eval(row)?;
+
+    // AND and OR only need the right side when the left one does not decide.
+    match (op, truth(&l)) {
+        (BinaryOp::And, Some(false)) => return Ok(from_bool(Some(false))),
+        (BinaryOp::Or, Some(true)) => return Ok(from_bool(Some(true))),
+        _ => {}
+    }
+
+    let r = right.eval(row)?;
+    let (l, r) = match op {
+        BinaryOp::And | BinaryOp::Or | BinaryOp::Like | BinaryOp::Glob => (l, r),
+        _ => coerce((l, left.affinity(row)), (r, right.affinity(row))),
+    };
+    let result = match op {
+        BinaryOp::And => and(truth(&l), truth(&r)),
+        BinaryOp::Or => or(truth(&l), truth(&r)),
+        BinaryOp::Eq => compare(&l, &r).map(|o| o.is_eq()),
+        BinaryOp::Ne => compare(&l, &r).map(|o| o.is_ne()),
+        BinaryOp::Lt => compare(&l, &r).map(|o| o.is_lt()),
+        BinaryOp::Le => compare(&l, &r).map(|o| o.is_le()),
+        BinaryOp::Gt => compare(&l, &r).map(|o| o.is_gt()),
+        BinaryOp::Ge => compare(&l, &r).map(|o| o.is_ge()),
+        BinaryOp::Is => Some(is(&l, &r)),
+        BinaryOp::IsNot => Some(!is(&l, &r)),
+        BinaryOp::Like => text(&l)
+            .zip(text(&r))
+            .map(|(value, pattern)| like(&pattern, &value)),
+        BinaryOp::Glob => text(&l)
+            .zip(text(&r))
+            .map(|(value, pattern)| glob(&pattern, &value)),
+    };
+    Ok(from_bool(result))
+}
+
+/// Whether a value is one of the items, comparing them like `=` does. The
+/// result is unknown when the value or an item it is not found among is NULL,
+/// unless there is no item at all.
+fn is_in(
+    value: (RecordValue, Affinity),
+    items: impl IntoIterator<Item = Result<(RecordValue, Affinity)>>,
+) -> Result<RecordValue> {
+    let mut result = Some(false);
+    for item in items {
+        let (value, item) = coerce(value.clone(), item?);
+        match compare(&value, &item) {
+            Some(Ordering::Equal) => return Ok(from_bool(Some(true))),
+            Some(_) => {}
+            None => result = None,
+        }
+    }
+    Ok(from_bool(result))
+}
+
+/// The affinity of the only column of a subquery whose value is used.
+fn single_column(result: &ResultSet) -> Result<Affinity> {
+    match result.columns.as_slice() {
+        [(_, affinity)] => Ok(*affinity),
+        columns => Err(err!(
+            "sub-select returns {} columns - expected 1",
+            columns.len()
+        )),
+    }
+}
+
+fn from_bool(value: Option<bool>) -> RecordValue {
+    match value {
+        Some(b) => RecordValue::Int(b as i64),
+        None => RecordValue::Null,
+    }
+}
+
+/// Truth value of a value in a boolean context, `None` being unknown.
+fn truth(value: &RecordValue) -> Option<bool> {
+    match value {
+        RecordValue::Null => None,
+        RecordValue::PrimaryKey(n) => Some(*n != 0),
+        RecordValue::Int(n) => Some(*n != 0),
+        RecordValue::Float(n) => Some(*n != 0.0),
+        RecordValue::Text(t) => Some(t.trim().parse::<f64>().is_ok_and(|n| n != 0.0)),
+        RecordValue::Blob(_) => Some(false),
+    }
+}
+
+fn and(l: Option<bool>, r: Option<bool>) -> Option<bool> {
+    match (l, r) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn or(l: Option<bool>, r: Option<bool>) -> Option<bool> {
+    match (l, r) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+fn is(l: &RecordValue, r: &RecordValue) -> bool {
+    match (l, r) {
+        (RecordValue::Null, RecordValue::Null) => true,
+        (RecordValue::Null, _) | (_, RecordValue::Null) => false,
+        _ => compare(l, r).is_some_and(|o| o.is_eq()),
+    }
+}
+
+/// Compares two values in SQLite's storage class order: numbers, then text,
+/// then blobs. Comparing with NULL gives `None`.
+fn compare(l: &RecordValue, r: &RecordValue) -> Option<Ordering> {
+    match (l, r) {
+        (RecordValue::Null, _) | (_, RecordValue::Null) => None,
+        _ => Some(l.cmp(r)),
+    }
+}
+
+/// Converts the operands of a comparison like SQLite does before comparing
+/// them: a column with a numeric affinity turns the other operand into a
+/// number when it can, and a text column turns an operand without affinity
+/// into text.
+fn coerce(
+    (l, l_affinity): (RecordValue, Affinity),
+    (r, r_affinity): (RecordValue, Affinity),
+) -> (RecordValue, RecordValue) {
+    use Affinity::*;
+
+    match (l_affinity, r_affinity) {
+        (a, Text | Blob) if a.is_numeric() => (l, r.apply_affinity(Numeric)),
+        (Text | Blob, a) if a.is_numeric() => (l.apply_affinity(Numeric), r),
+        (Text, Blob) => (l, r.apply_affinity(Text)),
+        (Blob, Text) => (l.apply_affinity(Text), r),
+        _ => (l, r),
+    }
+}
+
+fn text(value: &RecordValue) -> Option<String> {
+    match value {
+        RecordValue::Null => None,
+        RecordValue::Blob(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        v => Some(v.to_string()),
+    }
+}
+
+/// SQL `LIKE`: `%` matches any sequence, `_` any single character, and ASCII
+/// letters match regardless of case.
+fn like(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let value: Vec<char> = value.chars().map(|c| c.to_ascii_lowercase()).collect();
+    like_chars(&pattern, &value)
+}
+
+fn like_chars(pattern: &[char], value: &[char]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some(('%', rest)) => (0..=value.len()).any(|i| like_chars(rest, &value[i..])),
+        Some(('_', rest)) => !value.is_empty() && like_chars(rest, &value[1..]),
+        Some((c, rest)) => value.first() == Some(c) && like_chars(rest, &value[1..]),
+    }
+}
+
+/// SQL `GLOB`: case sensitive with `*`, `?` and `[...]` character classes.
+fn glob(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    glob_chars(&pattern, &value)
+}
+
+fn glob_chars(pattern: &[char], value: &[char]) -> bool {
+    match pattern.split_first() {
+        None => value.is_empty(),
+        Some(('*', rest)) => (0..=value.len()).any(|i| glob_chars(rest, &value[i..])),
+        Some(('?', rest)) => !value.is_empty() && glob_chars(rest, &value[1..]),
+        // A `]` right after the opening bracket belongs to the class.
+        Some(('[', rest)) => match rest.iter().skip(1).position(|&c| c == ']') {
+            Some(end) => {
+                let (class, rest) = (&rest[..end + 1], &rest[end + 2..]);
+                value.first().is_some_and(|&c| in_class(class, c)) && glob_chars(rest, &value[1..])
+            }
+            None => value.first() == Some(&'[') && glob_chars(rest, &value[1..]),
+        },
+        Some((c, rest)) => value.first() == Some(c) && glob_chars(rest, &value[1..]),
+    }
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    matched != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::collections::HashMap;
+
+    struct TestRow(HashMap<&'static str, RecordValue>);
+
+    impl Row for TestRow {
+        fn col(&self, _table: Option<&str>, name: &str) -> Result<RecordValue> {
+            self.0
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::NoSuchColumn(name.to_string()))
+        }
+
+        fn affinity(&self, _table: Option<&str>, name: &str) -> Affinity {
+            match name {
+                "id" => Affinity::Integer,
+                "name" | "code" => Affinity::Text,
+                _ => Affinity::Blob,
+            }
+        }
+    }
+
+    fn row() -> TestRow {
+        TestRow(HashMap::from([
+            ("id", RecordValue::PrimaryKey(3)),
+            ("name", RecordValue::Text("Fuji".into())),
+            ("code", RecordValue::Text("042".into())),
+            ("score", RecordValue::Float(7.5)),
+            ("color", RecordValue::Null),
+        ]))
+    }
+
+    fn col(name: &str) -> Expr {
+        Expr::column(name)
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Literal::Integer(n))
+    }
+
+    fn text(t: &str) -> Expr {
+        Expr::Literal(Literal::Text(t.into()))
+    }
+
+    fn null() -> Expr {
+        Expr::Literal(Literal::Null)
+    }
+
+    fn eval(expr: Expr) -> Option<bool> {
+        truth(&expr.eval(&row()).unwrap())
+    }
+
+    #[test]
+    fn it_compares_values() {
+        assert_eq!(
+            eval(Expr::binary(col("id"), BinaryOp::Eq, int(3))),
+            Some(true)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("id"), BinaryOp::Lt, int(3))),
+            Some(false)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("score"), BinaryOp::Gt, int(7))),
+            Some(true)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("name"), BinaryOp::Ne, text("Fuji"))),
+            Some(false)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("name"), BinaryOp::Gt, int(100))),
+            Some(true)
+        );
+        assert_eq!(eval(Expr::binary(col("color"), BinaryOp::Eq, null())), None);
+    }
+
+    #[test]
+    fn it_converts_values_by_column_affinity() {
+        assert_eq!(
+            eval(Expr::binary(col("id"), BinaryOp::Eq, text("3"))),
+            Some(true)
+        );
+        assert_eq!(
+            eval(Expr::binary(text(" 3.0"), BinaryOp::Eq, col("id"))),
+            Some(true)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("code"), BinaryOp::Eq, int(42))),
+            Some(false)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("code"), BinaryOp::Eq, text("042"))),
+            Some(true)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("code"), BinaryOp::Lt, int(5))),
+            Some(true)
+        );
+        // Without a column on either side, text is never equal to a number.
+        assert_eq!(
+            eval(Expr::binary(text("3"), BinaryOp::Eq, int(3))),
+            Some(false)
+        );
+        assert_eq!(
+            eval(Expr::Between {
+                expr: Box::new(col("id")),
+                low: Box::new(text("2")),
+                high: Box::new(text("10")),
+            }),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn it_sorts_values_across_storage_classes() {
+        let mut values = [
+            RecordValue::Blob(vec![0]),
+            RecordValue::Text("a".into()),
+            RecordValue::Float(2.5),
+            RecordValue::Null,
+            RecordValue::Int(3),
+            RecordValue::PrimaryKey(1),
+        ];
+        values.sort();
+        assert_eq!(
+            values.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec!["NULL", "1", "2.5", "3", "a", "[0]"]
+        );
+    }
+
+    #[test]
+    fn it_applies_three_valued_logic() {
+        let unknown = || Expr::binary(col("color"), BinaryOp::Eq, text("red"));
+        let yes = || Expr::binary(col("id"), BinaryOp::Eq, int(3));
+        let no = || Expr::binary(col("id"), BinaryOp::Eq, int(4));
+
+        assert_eq!(
+            eval(Expr::binary(unknown(), BinaryOp::And, no())),
+            Some(false)
+        );
+        assert_eq!(eval(Expr::binary(unknown(), BinaryOp::And, yes())), None);
+        assert_eq!(
+            eval(Expr::binary(unknown(), BinaryOp::Or, yes())),
+            Some(true)
+        );
+        assert_eq!(eval(Expr::binary(unknown(), BinaryOp::Or, no())), None);
+        assert_eq!(eval(Expr::not(unknown())), None);
+        assert_eq!(eval(Expr::not(no())), Some(true));
+    }
+
+    #[test]
+    fn it_evaluates_null_checks() {
+        assert_eq!(
+            eval(Expr::binary(col("color"), BinaryOp::Is, null())),
+            Some(true)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("name"), BinaryOp::Is, null())),
+            Some(false)
+        );
+        assert_eq!(
+            eval(Expr::binary(col("name"), BinaryOp::IsNot, null())),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn it_evaluates_in_lists() {
+        let in_list = |list| Expr::In {
+            expr: Box::new(col("id")),
+            list,
+        };
+        assert_eq!(eval(in_list(vec![int(1), int(3)])), Some(true));
+        assert_eq!(eval(in_list(vec![int(1), int(2)])), Some(false));
+        assert_eq!(eval(in_list(vec![int(1), null()])), None);
+        assert_eq!(eval(in_list(vec![null(), int(3)])), Some(true));
+    }
+
+    #[test]
+    fn it_evaluates_between() {
+        let between = |low, high| Expr::Between {
+            expr: Box::new(col("score")),
+            low: Box::new(low),
+            high: Box::new(high),
+        };
+        assert_eq!(eval(between(int(7), int(8))), Some(true));
+        assert_eq!(eval(between(int(8), int(9))), Some(false));
+        assert_eq!(eval(between(int(7), null())), None);
+        assert_eq!(eval(between(int(8), null())), Some(false));
+    }
+
+    #[test]
+    fn it_matches_like_patterns() {
+        assert!(like("fu%", "Fuji"));
+        assert!(like("%J_", "Fuji"));
+        assert!(like("%", ""));
+        assert!(!like("fu_", "Fuji"));
+        assert!(!like("%x%", "Fuji"));
+    }
+
+    #[test]
+    fn it_matches_glob_patterns() {
+        assert!(glob("F*", "Fuji"));
+        assert!(!glob("f*", "Fuji"));
+        assert!(glob("?uj?", "Fuji"));
+        assert!(glob("[A-Z]uji", "Fuji"));
+        assert!(glob("[^a-z]*", "Fuji"));
+        assert!(!glob("[abc]*", "Fuji"));
+        assert!(glob("F[u]ji", "Fuji"));
+    }
+}
@@ -0,0 +1,85 @@
+use super::{
+    expr::{Expr, Row},
+    Db, Error, RecordValue, Result,
+};
+use std::io::{Read, Seek, Write};
+
+/// `INSERT INTO table [(columns)] VALUES (values)[, (values)...]`, where no
+/// columns means every table column in order.
+#[derive(Debug, PartialEq)]
+pub struct Insert<'a> {
+    pub table: &'a str,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Expr>>,
+}
+
+impl Insert<'_> {
+    /// Inserts the rows and counts the change in the file header. Columns
+    /// without a value are NULL.
+    ///
+    /// Either every row is written to the file or, when one of them fails,
+    /// none is.
+    pub fn execute<R: Read + Write + Seek>(&self, db: &Db<R>) -> Result<()> {
+        match self.insert_rows(db) {
+            Ok(()) => db.commit(),
+            Err(err) => {
+                db.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    fn insert_rows<R: Read + Write + Seek>(&self, db: &Db<R>) -> Result<()> {
+        let table = db.table(self.table)?;
+        let names = table.column_names();
+
+        let positions = if self.columns.is_empty() {
+            (0..names.len()).collect()
+        } else {
+            self.columns
+                .iter()
+                .map(|col| {
+                    names
+                        .iter()
+                        .position(|name| name.eq_ignore_ascii_case(col))
+                        .ok_or_else(|| Error::NoSuchColumn(format!("{}.{col}", self.table)))
+                })
+                .collect::<Result<Vec<usize>>>()?
+        };
+
+        for exprs in &self.rows {
+            if exprs.len() != positions.len() {
+                return Err(if self.columns.is_empty() {
+                    err!(
+                        "table {} has {} columns but {} values were supplied",
+                        self.table,
+                        names.len(),
+                        exprs.len()
+                    )
+                } else {
+                    err!("{} values for {} columns", exprs.len(), positions.len())
+                });
+            }
+
+            let mut values = vec![RecordValue::Null; names.len()];
+            for (&pos, expr) in positions.iter().zip(exprs) {
+                values[pos] = expr.eval(&NoRow)?;
+            }
+            table.insert(values)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The values of an `INSERT` cannot refer to any column.
+struct NoRow;
+
+impl Row for NoRow {
+    fn col(&self, table: Option<&str>, name: &str) -> Result<RecordValue> {
+        Err(Error::NoSuchColumn(match table {
+            Some(table) => format!("{table}.{name}"),
+            None => name.to_string(),
+        }))
+    }
+}
@@ -0,0 +1,672 @@
+use super::{
+    create::ViewDefinition,
+    cte::Ctes,
+    expr::{BinaryOp, Expr, Literal, Row},
+    select::{FromTable, JoinConstraint, JoinKind, ResultSet, TableOrSubquery},
+    Affinity, Conditions, Db, Error, RecordValue, Result, Select, Table, TableRow,
+};
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    io::{Read, Seek},
+    rc::Rc,
+};
+
+/// The tables of a `FROM` clause, under the names their columns are qualified
+/// by, joined from left to right.
+#[derive(Debug)]
+pub struct Sources<'a, R: Read + Seek> {
+    db: &'a Db<R>,
+    sources: Vec<Source<'a, R>>,
+    /// Tables of the `WITH` clauses, which subqueries can read from too.
+    ctes: Ctes,
+    /// The query this one is a subquery of, whose columns it may refer to.
+    outer: Option<Outer<'a, R>>,
+    /// Results of the subqueries which refer to no column of this query, and
+    /// are the same for every row.
+    subqueries: RefCell<Vec<(Select, Rc<ResultSet>)>>,
+}
+
+/// The query a subquery runs for: the tables the columns of the subquery may
+/// refer to, the row giving their values, and whether any column did.
+pub struct Outer<'a, R: Read + Seek> {
+    sources: &'a Sources<'a, R>,
+    row: &'a dyn Row,
+    correlated: &'a Cell<bool>,
+}
+
+impl<R: Read + Seek> Clone for Outer<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<R: Read + Seek> Copy for Outer<'_, R> {}
+
+impl<R: Read + Seek> fmt::Debug for Outer<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Outer")
+            .field("correlated", &self.correlated)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct Source<'a, R: Read + Seek> {
+    name: String,
+    relation: Relation<'a, R>,
+    join: JoinKind,
+    /// The `ON` constraint, or the equality of the `USING` columns.
+    constraint: Option<Expr>,
+    /// Columns of a `USING` constraint, which unqualified names and `*` take
+    /// from the tables on the left.
+    using: Vec<String>,
+}
+
+/// What the rows of a source are read from.
+#[derive(Debug)]
+enum Relation<'a, R: Read + Seek> {
+    Table(Box<Table<'a, R>>),
+    /// The rows of a subquery, which runs once when the sources are opened,
+    /// or of a table of a `WITH` clause.
+    Derived(Rc<ResultSet>),
+}
+
+impl<'a, R: Read + Seek> Sources<'a, R> {
+    /// Opens the tables and runs the subqueries of the clause, which may refer
+    /// to the columns of the outer query when given one. A table of a `WITH`
+    /// clause hides a table or view of the database with the same name.
+    pub fn open(
+        db: &'a Db<R>,
+        from: &'a [FromTable],
+        ctes: Ctes,
+        outer: Option<Outer<'a, R>>,
+    ) -> Result<Self> {
+        let mut sources = Self {
+            db,
+            sources: vec![],
+            ctes,
+            outer,
+            subqueries: RefCell::default(),
+        };
+        for (idx, item) in from.iter().enumerate() {
+            let (name, relation) = match &item.table {
+                TableOrSubquery::Table(name) => match sources.ctes.get(name) {
+                    Some(result) => (name.clone(), Relation::Derived(Rc::clone(result))),
+                    None => match db.view(name)? {
+                        Some(view) => (
+                            view.name.clone(),
+                            Relation::Derived(Rc::new(view_rows(db, &view, &sources.ctes)?)),
+                        ),
+                        None => {
+                            let table = db.table(name)?;
+                            (table.name().to_string(), Relation::Table(Box::new(table)))
+                        }
+                    },
+                },
+                TableOrSubquery::Subquery(select) => (
+                    format!("(subquery-{})", idx + 1),
+                    Relation::Derived(Rc::new(select.query(db, &sources.ctes, outer)?)),
+                ),
+            };
+            sources.sources.push(Source {
+                name: item.alias.clone().unwrap_or(name),
+                relation,
+                join: item.join,
+                constraint: None,
+                using: vec![],
+            });
+        }
+
+        // Constraints may refer to any of the tables.
+        for (idx, item) in from.iter().enumerate() {
+            let constraint = match &item.constraint {
+                JoinConstraint::None => None,
+                JoinConstraint::On(expr) => Some(sources.resolve(expr, |_| None)?),
+                JoinConstraint::Using(names) => {
+                    let terms = names
+                        .iter()
+                        .map(|name| sources.using_term(idx, name))
+                        .collect::<Result<Vec<Expr>>>()?;
+                    sources.sources[idx].using = names.clone();
+                    terms
+                        .into_iter()
+                        .reduce(|a, b| Expr::binary(a, BinaryOp::And, b))
+                }
+            };
+            sources.sources[idx].constraint = constraint;
+        }
+
+        Ok(sources)
+    }
+
+    /// The expression with every column qualified by the name of its table.
+    /// A name which is no column stands for the expression `alias` gives, if
+    /// any, and else for a column of the outer queries.
+    pub fn resolve(&self, expr: &Expr, alias: impl Fn(&str) -> Option<Expr>) -> Result<Expr> {
+        expr.map_columns(&|table, name| {
+            if let Some(idx) = self.source_of(table, name)? {
+                return Ok(Some(self.column(idx, name)));
+            }
+            match alias(name) {
+                Some(expr) => Ok(Some(expr)),
+                None => self
+                    .outer_column(table, name)?
+                    .map(Some)
+                    .ok_or_else(|| no_such_column(table, name)),
+            }
+        })
+    }
+
+    /// Affinity of the values of a resolved expression, which only a column
+    /// has.
+    pub fn affinity(&self, expr: &Expr) -> Affinity {
+        match expr {
+            Expr::Column { table, name } => self.column_affinity(table.as_deref(), name),
+            _ => Affinity::Blob,
+        }
+    }
+
+    /// The result of a subquery run for a row of this query. A subquery which
+    /// refers to no column of the outer queries only runs once.
+    pub fn subquery(&self, select: &Select, row: &dyn Row) -> Result<Rc<ResultSet>> {
+        let cached = self
+            .subqueries
+            .borrow()
+            .iter()
+            .find(|(s, _)| s == select)
+            .map(|(_, result)| Rc::clone(result));
+        if let Some(result) = cached {
+            return Ok(result);
+        }
+
+        let correlated = Cell::new(false);
+        let outer = Outer {
+            sources: self,
+            row,
+            correlated: &correlated,
+        };
+        let result = Rc::new(select.query(self.db, &self.ctes, Some(outer))?);
+        if !correlated.get() {
+            self.subqueries
+                .borrow_mut()
+                .push((select.clone(), Rc::clone(&result)));
+        }
+        Ok(result)
+    }
+
+    /// The columns `*` stands for, or `table.*` when given a table.
+    pub fn all_columns(&self, table: Option<&str>) -> Result<Vec<Expr>> {
+        let Some(table) = table else {
+            return Ok((0..self.sources.len())
+                .flat_map(|idx| {
+                    let source = &self.sources[idx];
+                    source
+                        .relation
+                        .column_names()
+                        .into_iter()
+                        .filter(|name| !source.is_using(name))
+                        .map(move |name| self.column(idx, name))
+                })
+                .collect());
+        };
+
+        let idx = self
+            .position(table)
+            .ok_or_else(|| Error::NoSuchTable(table.to_string()))?;
+        Ok(self.sources[idx]
+            .relation
+            .column_names()
+            .into_iter()
+            .map(|name| self.column(idx, name))
+            .collect())
+    }
+
+    /// Calls `f` with each row the joins make, as long as it returns true.
+    ///
+    /// Joins are nested loops over the tables from left to right. The rows of
+    /// each table are searched with the terms of the clause and of its join
+    /// constraint that refer to it, the columns of the tables before it being
+    /// replaced by their values, so that a join on an indexed column or on the
+    /// rowid seeks the rows to join.
+    pub fn join<'s>(
+        &'s self,
+        conditions: &Conditions,
+        f: &mut dyn FnMut(JoinedRow<'s, R>) -> Result<bool>,
+    ) -> Result<()> {
+        let terms = self.search_terms(conditions);
+        self.join_from(0, vec![], &terms, f).map(|_| ())
+    }
+
+    fn join_from<'s>(
+        &'s self,
+        level: usize,
+        rows: Vec<Option<Rc<SourceRow<'s, R>>>>,
+        terms: &[Vec<&Expr>],
+        f: &mut dyn FnMut(JoinedRow<'s, R>) -> Result<bool>,
+    ) -> Result<bool> {
+        let Some(source) = self.sources.get(level) else {
+            return f(JoinedRow {
+                sources: self,
+                rows,
+            });
+        };
+
+        let conditions = self.bind(level, &rows, &terms[level]);
+        let mut matched = false;
+        for row in source.relation.search_rows(&conditions)? {
+            let mut joined = JoinedRow {
+                sources: self,
+                rows: rows.clone(),
+            };
+            joined.rows.push(Some(Rc::new(row?)));
+            if let Some(constraint) = &source.constraint {
+                if !constraint.is_true(&joined)? {
+                    continue;
+                }
+            }
+
+            matched = true;
+            if !self.join_from(level + 1, joined.rows, terms, f)? {
+                return Ok(false);
+            }
+        }
+
+        // A left join keeps the rows on the left without a match, with NULL
+        // for every column of the table.
+        if source.join == JoinKind::Left && !matched {
+            let mut rows = rows;
+            rows.push(None);
+            return self.join_from(level + 1, rows, terms, f);
+        }
+        Ok(true)
+    }
+
+    /// For each table, the terms of its join constraint and of the `WHERE`
+    /// clause which refer to it and to no table after it. Terms of the clause
+    /// do not narrow down the table on the right of a left join, whose missing
+    /// rows still make a row.
+    fn search_terms<'e>(&'e self, conditions: &'e Conditions) -> Vec<Vec<&'e Expr>> {
+        let clause: Vec<&Expr> = conditions.0.iter().flat_map(Expr::conjuncts).collect();
+
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(level, source)| {
+                let clause = match source.join {
+                    JoinKind::Inner => clause.as_slice(),
+                    JoinKind::Left => &[],
+                };
+                source
+                    .constraint
+                    .iter()
+                    .flat_map(Expr::conjuncts)
+                    .chain(clause.iter().copied())
+                    .filter(|term| self.can_narrow(level, term))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Whether a term can narrow down the rows of a table, the columns of the
+    /// outer queries having a single value like those of the tables before.
+    fn can_narrow(&self, level: usize, term: &Expr) -> bool {
+        let levels: Vec<Option<usize>> = term
+            .columns()
+            .into_iter()
+            .map(|(table, name)| self.index(table, name))
+            .collect();
+        if !levels.contains(&Some(level)) || levels.iter().flatten().any(|&l| l > level) {
+            return false;
+        }
+
+        // Once replaced by its value, a column with a numeric affinity would
+        // be compared with a text column as text rather than as a number.
+        match term {
+            Expr::Binary { left, right, .. } => {
+                let affinity = |expr: &Expr| match expr {
+                    Expr::Column { table, name } => Some((
+                        self.index(table.as_deref(), name),
+                        self.column_affinity(table.as_deref(), name),
+                    )),
+                    _ => None,
+                };
+                let level = Some(level);
+                match (affinity(left), affinity(right)) {
+                    (Some((l, a)), Some((r, b))) if l == level && r != level => {
+                        a.is_numeric() || !b.is_numeric()
+                    }
+                    (Some((l, a)), Some((r, b))) if r == level && l != level => {
+                        b.is_numeric() || !a.is_numeric()
+                    }
+                    _ => true,
+                }
+            }
+            _ => true,
+        }
+    }
+
+    /// The conditions on a table, with the columns of the tables before it and
+    /// of the outer queries replaced by their values in the rows joined so
+    /// far. A term with a value no literal can stand for is left out.
+    fn bind(
+        &self,
+        level: usize,
+        rows: &[Option<Rc<SourceRow<'_, R>>>],
+        terms: &[&Expr],
+    ) -> Conditions {
+        let bound = terms.iter().filter_map(|term| {
+            term.map_columns(&|table, name| {
+                let value = match self.index(table, name) {
+                    Some(idx) if idx == level => return Ok(Some(Expr::column(name))),
+                    Some(idx) => match &rows[idx] {
+                        Some(row) => row.col(name)?,
+                        None => RecordValue::Null,
+                    },
+                    None => self.outer_value(table, name)?,
+                };
+                Literal::from_value(&value)
+                    .map(|lit| Some(Expr::Literal(lit)))
+                    .ok_or_else(|| err!("no literal for {value}"))
+            })
+            .ok()
+        });
+
+        Conditions(bound.reduce(|a, b| Expr::binary(a, BinaryOp::And, b)))
+    }
+
+    /// The equality of a `USING` column of a table and of the first table on
+    /// its left which has it.
+    fn using_term(&self, idx: usize, name: &str) -> Result<Expr> {
+        let left = self.sources[..idx]
+            .iter()
+            .position(|s| s.relation.has_column(name) && !s.is_using(name));
+        match left {
+            Some(left) if self.sources[idx].relation.has_column(name) => Ok(Expr::binary(
+                self.column(left, name),
+                BinaryOp::Eq,
+                self.column(idx, name),
+            )),
+            _ => Err(err!(
+                "cannot join using column {name} - column not present in both tables"
+            )),
+        }
+    }
+
+    /// The table a column belongs to, or none when no table has it.
+    fn source_of(&self, table: Option<&str>, name: &str) -> Result<Option<usize>> {
+        if let Some(table) = table {
+            return Ok(self
+                .position(table)
+                .filter(|&idx| self.sources[idx].relation.has_column(name)));
+        }
+
+        let mut found = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.relation.has_column(name) && !s.is_using(name))
+            .map(|(idx, _)| idx);
+        match (found.next(), found.next()) {
+            (Some(_), Some(_)) => Err(err!("ambiguous column name: {name}")),
+            (idx, _) => Ok(idx),
+        }
+    }
+
+    /// A column of the outer queries, which makes this query correlated with
+    /// each of them up to the one the column belongs to.
+    fn outer_column(&self, table: Option<&str>, name: &str) -> Result<Option<Expr>> {
+        let Some(outer) = self.outer else {
+            return Ok(None);
+        };
+        let column = match outer.sources.source_of(table, name)? {
+            Some(idx) => Some(outer.sources.column(idx, name)),
+            None => outer.sources.outer_column(table, name)?,
+        };
+        if column.is_some() {
+            outer.correlated.set(true);
+        }
+        Ok(column)
+    }
+
+    /// The value of a column of the outer queries in the rows they run this
+    /// query for.
+    fn outer_value(&self, table: Option<&str>, name: &str) -> Result<RecordValue> {
+        match self.outer {
+            Some(outer) => outer.row.col(table, name),
+            None => Err(no_such_column(table, name)),
+        }
+    }
+
+    /// The table of a column that was resolved, or none for a column of the
+    /// outer queries.
+    fn index(&self, table: Option<&str>, name: &str) -> Option<usize> {
+        match table {
+            Some(table) => self.position(table),
+            None => self.source_of(None, name).ok().flatten(),
+        }
+    }
+
+    fn column_affinity(&self, table: Option<&str>, name: &str) -> Affinity {
+        match (self.index(table, name), self.outer) {
+            (Some(idx), _) => self.sources[idx].relation.affinity(name),
+            (None, Some(outer)) => outer.row.affinity(table, name),
+            (None, None) => Affinity::Blob,
+        }
+    }
+
+    fn position(&self, table: &str) -> Option<usize> {
+        self.sources
+            .iter()
+            .position(|s| s.name.eq_ignore_ascii_case(table))
+    }
+
+    fn column(&self, idx: usize, name: &str) -> Expr {
+        Expr::Column {
+            table: Some(self.sources[idx].name.clone()),
+            name: name.to_string(),
+        }
+    }
+}
+
+impl<R: Read + Seek> Source<'_, R> {
+    fn is_using(&self, name: &str) -> bool {
+        self.using.iter().any(|col| col.eq_ignore_ascii_case(name))
+    }
+}
+
+impl<R: Read + Seek> Relation<'_, R> {
+    fn has_column(&self, name: &str) -> bool {
+        match self {
+            Self::Table(table) => table.has_column(name),
+            Self::Derived(result) => result.position(name).is_some(),
+        }
+    }
+
+    fn column_names(&self) -> Vec<&str> {
+        match self {
+            Self::Table(table) => table.column_names(),
+            Self::Derived(result) => result
+                .columns
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect(),
+        }
+    }
+
+    fn affinity(&self, name: &str) -> Affinity {
+        match self {
+            Self::Table(table) => table.affinity(name),
+            Self::Derived(result) => result
+                .position(name)
+                .map_or(Affinity::Blob, |idx| result.columns[idx].1),
+        }
+    }
+
+    /// The rows which may satisfy the conditions. The rows of a subquery are
+    /// all read.
+    fn search_rows<'s>(
+        &'s self,
+        conditions: &Conditions,
+    ) -> Result<Box<dyn Iterator<Item = Result<SourceRow<'s, R>>> + 's>> {
+        Ok(match self {
+            Self::Table(table) => Box::new(
+                table
+                    .search_rows(conditions)?
+                    .map(|row| row.map(SourceRow::Table)),
+            ),
+            Self::Derived(result) => Box::new(
+                result
+                    .rows
+                    .iter()
+                    .map(move |values| Ok(SourceRow::Derived(result, values))),
+            ),
+        })
+    }
+}
+
+#[derive(Debug)]
+enum SourceRow<'s, R: Read + Seek> {
+    Table(TableRow<'s, R>),
+    Derived(&'s ResultSet, &'s [RecordValue]),
+}
+
+impl<R: Read + Seek> SourceRow<'_, R> {
+    fn col(&self, name: &str) -> Result<RecordValue> {
+        match self {
+            Self::Table(row) => row.col(name),
+            Self::Derived(result, values) => result
+                .position(name)
+                .map(|idx| values[idx].clone())
+                .ok_or_else(|| Error::NoSuchColumn(name.to_string())),
+        }
+    }
+}
+
+/// A row of each table of the joins, where a table on the right of a left
+/// join without a match has none.
+#[derive(Debug)]
+pub struct JoinedRow<'s, R: Read + Seek> {
+    sources: &'s Sources<'s, R>,
+    rows: Vec<Option<Rc<SourceRow<'s, R>>>>,
+}
+
+impl<'s, R: Read + Seek> JoinedRow<'s, R> {
+    /// A row without a row of any table, whose columns are all NULL.
+    pub fn empty(sources: &'s Sources<'s, R>) -> Self {
+        Self {
+            sources,
+            rows: vec![],
+        }
+    }
+}
+
+impl<R: Read + Seek> Row for JoinedRow<'_, R> {
+    fn col(&self, table: Option<&str>, name: &str) -> Result<RecordValue> {
+        match self.sources.index(table, name) {
+            Some(idx) => match self.rows.get(idx) {
+                Some(Some(row)) => row.col(name),
+                _ => Ok(RecordValue::Null),
+            },
+            None => self.sources.outer_value(table, name),
+        }
+    }
+
+    fn affinity(&self, table: Option<&str>, name: &str) -> Affinity {
+        self.sources.column_affinity(table, name)
+    }
+
+    fn query(&self, select: &Select) -> Result<Rc<ResultSet>> {
+        self.sources.subquery(select, self)
+    }
+}
+
+/// The rows of a view, named by the columns of its definition when it has
+/// them. A view only reads from the database, whatever query refers to it.
+fn view_rows<R: Read + Seek>(db: &Db<R>, view: &ViewDefinition, ctes: &Ctes) -> Result<ResultSet> {
+    let mut result = view.select.query(db, &ctes.view(&view.name)?, None)?;
+    if !view.columns.is_empty() {
+        if view.columns.len() != result.columns.len() {
+            return Err(err!(
+                "expected {} columns for '{}' but got {}",
+                view.columns.len(),
+                view.name,
+                result.columns.len()
+            ));
+        }
+        for ((name, _), column) in result.columns.iter_mut().zip(&view.columns) {
+            name.clone_from(column);
+        }
+    }
+    Ok(result)
+}
+
+fn no_such_column(table: Option<&str>, name: &str) -> Error {
+    Error::NoSuchColumn(match table {
+        Some(table) => format!("{table}.{name}"),
+        None => name.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{sql::query, Error, Result};
+
+    #[test]
+    fn it_joins_tables() -> Result<()> {
+        assert_eq!(
+            query(
+                "select a.name, o.name from apples a join oranges o on o.id = a.id \
+                 where o.description like '%snacking' order by a.id"
+            )?,
+            vec!["Granny Smith|Mandarin", "Golden Delicious|Clementine"]
+        );
+        assert_eq!(
+            query("select count(*) from apples, oranges where apples.id < oranges.id")?,
+            vec!["14"]
+        );
+        assert_eq!(
+            query(
+                "select apples.*, oranges.name from oranges join apples using (id) where id = 2"
+            )?,
+            vec!["2|Fuji|Red|Tangelo"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_keeps_unmatched_rows_of_a_left_join() -> Result<()> {
+        assert_eq!(
+            query(
+                "select o.name, a.name from oranges o left join apples a using (id) \
+                 where o.id > 3 order by o.id"
+            )?,
+            vec![
+                "Clementine|Golden Delicious",
+                "Valencia Orange|",
+                "Navel Orange|"
+            ]
+        );
+        assert_eq!(
+            query(
+                "select o.id from oranges o left join apples a on a.id = o.id where a.id is null"
+            )?,
+            vec!["5", "6"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_resolves_columns_to_their_table() {
+        let err = query("select name from apples, oranges").unwrap_err();
+        assert!(err.to_string().ends_with("ambiguous column name: name"));
+        assert!(matches!(
+            query("select o.color from apples a join oranges o on o.id = a.id"),
+            Err(Error::NoSuchColumn(name)) if name == "o.color"
+        ));
+        assert!(matches!(
+            query("select a.name from apples join oranges on 1"),
+            Err(Error::NoSuchColumn(name)) if name == "a.name"
+        ));
+    }
+}
@@ -1,103 +1,399 @@
+mod aggregate;
+pub mod create;
+pub mod cte;
+pub mod expr;
+mod insert;
+mod join;
 pub mod parsers;
+mod select;
 
 use super::{
-    db::{Db, TableRow},
-    err, Result,
+    db::{Affinity, Db, RecordValue, Table, TableRow},
+    Error, Result,
+};
+use expr::{BinaryOp, Expr, Literal, Row};
+use nom::Parser;
+use std::{
+    cmp::Ordering,
+    io::{Read, Seek, Write},
+    ops::Bound,
+};
+
+pub use insert::Insert;
+pub use select::{
+    Compound, CompoundOp, FromTable, JoinConstraint, JoinKind, Limit, OrderingTerm, ResultColumn,
+    Select, TableOrSubquery,
 };
-use std::io::{Read, Seek};
 
 #[derive(Debug)]
 pub enum Sql<'a> {
-    Select {
-        columns: Vec<&'a str>,
-        table: &'a str,
-        conditions: Conditions,
-    },
+    Select(Box<Select>),
+    Insert(Insert<'a>),
 }
 
 impl<'a> Sql<'a> {
     pub fn new(s: &'a str) -> Result<Self> {
-        let (_, (columns, table, conditions)) =
-            parsers::parse_select(s).map_err(|e| err!("{e}"))?;
-        Ok(Self::Select {
-            columns,
-            table,
-            conditions: Conditions(conditions.into_iter().map(Condition::new).collect()),
-        })
-    }
-
-    pub fn execute<R: Read + Seek>(self, db: &Db<R>) -> Result<Vec<String>> {
-        let Self::Select {
-            columns,
-            table: tbl_name,
-            conditions,
-        } = self;
-        let table = db.table(tbl_name)?;
-        let rows = table
-            .search_rows(&conditions)?
-            .filter(|row| conditions.satisfy(row));
-
-        let outputs = if count_rows(&columns) {
-            vec![rows.count().to_string()]
-        } else {
-            rows.map(|row| {
-                columns
+        // Unlike `alt`, which keeps the error of the last parser, the error is
+        // the one of the parser that got furthest into the statement.
+        let (remaining, sql) = parsers::parse_select
+            .map(|select| Self::Select(Box::new(select)))
+            .parse(s)
+            .or_else(|select_err| {
+                parsers::parse_insert
+                    .map(Self::Insert)
+                    .parse(s)
+                    .map_err(|insert_err| furthest(select_err, insert_err))
+            })
+            .map_err(|e| Error::parse(s, e))?;
+
+        let remaining = remaining.trim_start().trim_start_matches(';').trim_start();
+        if !remaining.is_empty() {
+            return Err(Error::Parse {
+                position: s.len() - remaining.len(),
+                expected: "end of statement".to_string(),
+            });
+        }
+
+        Ok(sql)
+    }
+
+    pub fn execute<R: Read + Write + Seek>(self, db: &Db<R>) -> Result<Vec<String>> {
+        let select = match self {
+            Self::Select(select) => select,
+            Self::Insert(insert) => return insert.execute(db).map(|_| vec![]),
+        };
+
+        Ok(select
+            .execute(db)?
+            .iter()
+            .map(|values| {
+                values
                     .iter()
-                    .filter_map(|name| row.col(name).ok())
-                    .map(|v| format!("{v}"))
+                    .map(|v| match v {
+                        // NULL is printed as an empty string like the sqlite3 shell.
+                        RecordValue::Null => String::new(),
+                        v => v.to_string(),
+                    })
                     .collect::<Vec<String>>()
                     .join("|")
             })
-            .collect()
-        };
+            .collect())
+    }
+}
 
-        Ok(outputs)
+/// Of two parser errors, the one which got further into the input.
+fn furthest<'a>(
+    a: nom::Err<nom::error::Error<&'a str>>,
+    b: nom::Err<nom::error::Error<&'a str>>,
+) -> nom::Err<nom::error::Error<&'a str>> {
+    let remaining = |err: &nom::Err<nom::error::Error<&str>>| match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.input.len(),
+        nom::Err::Incomplete(_) => 0,
+    };
+    if remaining(&b) < remaining(&a) {
+        b
+    } else {
+        a
     }
 }
 
-fn count_rows(cols: &[&str]) -> bool {
-    cols.iter().any(|c| c.to_lowercase().as_str() == "count(*)")
+/// The `WHERE` clause of a statement. No clause means every row satisfies it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Conditions(Option<Expr>);
+
+impl Conditions {
+    fn satisfy<T: Row>(&self, row: &T) -> Result<bool> {
+        match &self.0 {
+            Some(expr) => expr.is_true(row),
+            None => Ok(true),
+        }
+    }
+
+    /// The range of values a column must be in for the whole clause to hold,
+    /// taken from comparisons with literals, `BETWEEN` and prefix `LIKE` terms
+    /// joined by `AND` at the top level. Rows in the range still have to be
+    /// checked against the clause.
+    ///
+    /// Literals are converted by the affinity of the column as they are when
+    /// compared with it. `LIKE` compares the text of any value, so a prefix
+    /// only bounds a column whose values are all stored as text.
+    pub fn range(&self, col: &str, affinity: Affinity) -> Option<KeyRange> {
+        let mut range = KeyRange::default();
+        let text_only = affinity == Affinity::Text;
+        let numeric = if affinity.is_numeric() {
+            Affinity::Numeric
+        } else {
+            affinity
+        };
+        let mut terms = vec![self.0.as_ref()?];
+        // Columns of the table are not qualified.
+        let is_col = |expr: &Expr| matches!(expr, Expr::Column { table: None, name } if name.eq_ignore_ascii_case(col));
+
+        while let Some(term) = terms.pop() {
+            match term {
+                Expr::Binary { left, op, right } => match (left.as_ref(), op, right.as_ref()) {
+                    (l, BinaryOp::And, r) => terms.extend([l, r]),
+                    (l, BinaryOp::Like, Expr::Literal(Literal::Text(pattern)))
+                        if text_only && is_col(l) =>
+                    {
+                        range.narrow_to_prefix(pattern);
+                    }
+                    (l, op, Expr::Literal(lit)) if is_col(l) => {
+                        range.narrow(*op, RecordValue::from(lit).apply_affinity(numeric))
+                    }
+                    (Expr::Literal(lit), op, r) if is_col(r) => {
+                        let value = RecordValue::from(lit).apply_affinity(numeric);
+                        match op {
+                            BinaryOp::Lt => range.narrow(BinaryOp::Gt, value),
+                            BinaryOp::Le => range.narrow(BinaryOp::Ge, value),
+                            BinaryOp::Gt => range.narrow(BinaryOp::Lt, value),
+                            BinaryOp::Ge => range.narrow(BinaryOp::Le, value),
+                            op => range.narrow(*op, value),
+                        }
+                    }
+                    _ => {}
+                },
+                Expr::Between { expr, low, high } if is_col(expr) => {
+                    if let (Expr::Literal(low), Expr::Literal(high)) = (low.as_ref(), high.as_ref())
+                    {
+                        range.narrow(BinaryOp::Ge, RecordValue::from(low).apply_affinity(numeric));
+                        range.narrow(
+                            BinaryOp::Le,
+                            RecordValue::from(high).apply_affinity(numeric),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        range.is_bounded().then_some(range)
+    }
 }
 
+/// Bounds on the values of a column, which are the keys to visit in an index
+/// on that column.
 #[derive(Debug)]
-pub enum Condition {
-    Eq { col: String, value: String },
+pub struct KeyRange {
+    lower: Bound<RecordValue>,
+    upper: Bound<RecordValue>,
 }
 
-impl Condition {
-    fn new((col, value): (&str, &str)) -> Self {
-        Self::Eq {
-            col: col.into(),
-            value: value.into(),
+impl Default for KeyRange {
+    fn default() -> Self {
+        Self {
+            lower: Bound::Unbounded,
+            upper: Bound::Unbounded,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct Conditions(Vec<Condition>);
+impl KeyRange {
+    /// Whether the key comes before every key in the range.
+    pub fn is_below(&self, key: &RecordValue) -> bool {
+        match &self.lower {
+            Bound::Included(v) => key.cmp(v).is_lt(),
+            Bound::Excluded(v) => key.cmp(v).is_le(),
+            Bound::Unbounded => false,
+        }
+    }
 
-impl Conditions {
-    fn satisfy<R: Read + Seek>(&self, row: &TableRow<'_, R>) -> bool {
-        self.0.iter().all(|condition| match condition {
-            Condition::Eq { col, value } => row.col(col).is_ok_and(|v| v == value.as_str()),
-        })
+    /// Whether the key comes after every key in the range.
+    pub fn is_above(&self, key: &RecordValue) -> bool {
+        match &self.upper {
+            Bound::Included(v) => key.cmp(v).is_gt(),
+            Bound::Excluded(v) => key.cmp(v).is_ge(),
+            Bound::Unbounded => false,
+        }
     }
 
-    pub fn cols(&self) -> Vec<&str> {
-        self.0
-            .iter()
-            .map(|c| match c {
-                Condition::Eq { col, .. } => col.as_str(),
-            })
-            .collect()
+    /// The only value in the range, when both bounds include the same value.
+    pub fn equal_value(&self) -> Option<&RecordValue> {
+        match (&self.lower, &self.upper) {
+            (Bound::Included(l), Bound::Included(u)) if l.cmp(u).is_eq() => Some(l),
+            _ => None,
+        }
     }
 
-    pub fn values(&self) -> Vec<&str> {
-        self.0
-            .iter()
-            .map(|c| match c {
-                Condition::Eq { value, .. } => value.as_str(),
-            })
+    /// The value of the lower bound, whether it is included or not.
+    pub fn lower_value(&self) -> Option<&RecordValue> {
+        match &self.lower {
+            Bound::Included(v) | Bound::Excluded(v) => Some(v),
+            Bound::Unbounded => None,
+        }
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        !matches!(
+            (&self.lower, &self.upper),
+            (Bound::Unbounded, Bound::Unbounded)
+        )
+    }
+
+    /// Narrows the range to the keys satisfying `key op value`. Comparisons
+    /// with NULL never hold and are left to the clause itself.
+    fn narrow(&mut self, op: BinaryOp, value: RecordValue) {
+        if value == RecordValue::Null {
+            return;
+        }
+
+        match op {
+            BinaryOp::Eq | BinaryOp::Is => {
+                self.narrow_lower(Bound::Included(value.clone()));
+                self.narrow_upper(Bound::Included(value));
+            }
+            BinaryOp::Gt => self.narrow_lower(Bound::Excluded(value)),
+            BinaryOp::Ge => self.narrow_lower(Bound::Included(value)),
+            BinaryOp::Lt => self.narrow_upper(Bound::Excluded(value)),
+            BinaryOp::Le => self.narrow_upper(Bound::Included(value)),
+            _ => {}
+        }
+    }
+
+    /// Narrows the range to text starting with the literal prefix of a `LIKE`
+    /// pattern. ASCII letters match either case, so the range runs from the
+    /// prefix in upper case to past the prefix in lower case.
+    fn narrow_to_prefix(&mut self, pattern: &str) {
+        let prefix: String = pattern
+            .chars()
+            .take_while(|c| !matches!(c, '%' | '_'))
+            .collect();
+        if prefix.is_empty() {
+            return;
+        }
+
+        self.narrow_lower(Bound::Included(RecordValue::Text(
+            prefix.to_ascii_uppercase(),
+        )));
+
+        let mut upper = prefix.to_ascii_lowercase();
+        if let Some(next) = upper.pop().and_then(|c| char::from_u32(c as u32 + 1)) {
+            upper.push(next);
+            self.narrow_upper(Bound::Excluded(RecordValue::Text(upper)));
+        }
+    }
+
+    fn narrow_lower(&mut self, bound: Bound<RecordValue>) {
+        if is_tighter(&bound, &self.lower, Ordering::Greater) {
+            self.lower = bound;
+        }
+    }
+
+    fn narrow_upper(&mut self, bound: Bound<RecordValue>) {
+        if is_tighter(&bound, &self.upper, Ordering::Less) {
+            self.upper = bound;
+        }
+    }
+}
+
+/// Whether a bound leaves out more keys than another one, a tighter value
+/// being ordered toward the inside of the range.
+fn is_tighter(new: &Bound<RecordValue>, old: &Bound<RecordValue>, inward: Ordering) -> bool {
+    match (new, old) {
+        (Bound::Unbounded, _) => false,
+        (_, Bound::Unbounded) => true,
+        (Bound::Included(n) | Bound::Excluded(n), Bound::Included(o) | Bound::Excluded(o)) => {
+            match n.cmp(o) {
+                Ordering::Equal => matches!((new, old), (Bound::Excluded(_), Bound::Included(_))),
+                ord => ord == inward,
+            }
+        }
+    }
+}
+
+/// Runs a statement on the sample database and prints its rows like the shell.
+#[cfg(test)]
+fn query(sql: &str) -> Result<Vec<String>> {
+    let db = crate::db::DbFile::from_path("sample.db")?;
+    Sql::new(sql)?.execute(&db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(clause: &str, col: &str, affinity: Affinity) -> Option<KeyRange> {
+        let (_, expr) = parsers::parse_expr(clause).unwrap();
+        Conditions(Some(expr)).range(col, affinity)
+    }
+
+    /// Which of the keys are in the range.
+    fn keys_in(range: &KeyRange, keys: &[RecordValue]) -> Vec<String> {
+        keys.iter()
+            .filter(|key| !range.is_below(key) && !range.is_above(key))
+            .map(|key| key.to_string())
             .collect()
     }
+
+    #[test]
+    fn it_reports_where_a_statement_fails_to_parse() {
+        let position = |sql| match Sql::new(sql) {
+            Err(Error::Parse { position, .. }) => Some(position),
+            _ => None,
+        };
+
+        assert_eq!(position("select name from apples where id ="), Some(34));
+        // Like in SQLite, the misspelt keyword is read as an alias of the table.
+        assert_eq!(position("select name from apples wher id = 1"), Some(29));
+        assert_eq!(position("select name from apples a join"), Some(30));
+        assert_eq!(position("insert into apples values (1,"), Some(28));
+        assert_eq!(position("selec name from apples"), Some(0));
+    }
+
+    #[test]
+    fn it_bounds_a_column_by_comparisons() {
+        let keys: Vec<RecordValue> = (1..=6).map(RecordValue::Int).collect();
+
+        let r = range(
+            "year > 2 and year <= 5 and name = 'x'",
+            "year",
+            Affinity::Integer,
+        )
+        .unwrap();
+        assert_eq!(keys_in(&r, &keys), vec!["3", "4", "5"]);
+
+        let r = range(
+            "4 > year and year > 1 and year >= 1",
+            "year",
+            Affinity::Integer,
+        )
+        .unwrap();
+        assert_eq!(keys_in(&r, &keys), vec!["2", "3"]);
+
+        let r = range("year between 2 and 3", "year", Affinity::Integer).unwrap();
+        assert_eq!(keys_in(&r, &keys), vec!["2", "3"]);
+
+        let r = range("year = 4", "year", Affinity::Integer).unwrap();
+        assert_eq!(keys_in(&r, &keys), vec!["4"]);
+        assert_eq!(r.equal_value().unwrap().to_string(), "4");
+        assert!(!r.is_above(&RecordValue::Float(4.0)));
+
+        assert!(range("year > 2 or year < 1", "year", Affinity::Integer).is_none());
+        assert!(range("name > 'a'", "year", Affinity::Integer).is_none());
+        assert!(range("year > null", "year", Affinity::Integer).is_none());
+
+        // Text compared with a numeric column is compared as a number.
+        let r = range("year >= '5'", "year", Affinity::Integer).unwrap();
+        assert_eq!(keys_in(&r, &keys), vec!["5", "6"]);
+    }
+
+    #[test]
+    fn it_bounds_a_text_column_by_a_like_prefix() {
+        let keys: Vec<RecordValue> = ["JA", "JApan", "Jb", "jam", "japan", "jb", "k"]
+            .into_iter()
+            .map(|t| RecordValue::Text(t.into()))
+            .collect();
+
+        let r = range("country like 'ja%'", "country", Affinity::Text).unwrap();
+        assert_eq!(
+            keys_in(&r, &keys),
+            vec!["JA", "JApan", "Jb", "jam", "japan"]
+        );
+
+        let r = range("country like 'jap_n'", "country", Affinity::Text).unwrap();
+        assert_eq!(keys_in(&r, &keys), vec!["JApan", "Jb", "jam", "japan"]);
+
+        assert!(range("country like 'ja%'", "country", Affinity::Blob).is_none());
+        assert!(range("country like '%an'", "country", Affinity::Text).is_none());
+    }
 }
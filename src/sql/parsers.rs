@@ -1,61 +1,227 @@
+use super::{
+    create::{
+        ColumnConstraint, ColumnDefinition, ForeignKey, IndexedColumn, TableConstraint,
+        TableDefinition, ViewDefinition,
+    },
+    cte::CommonTableExpr,
+    expr::{BinaryOp, Expr, Literal},
+    Compound, CompoundOp, Conditions, FromTable, Insert, JoinConstraint, JoinKind, Limit,
+    OrderingTerm, ResultColumn, Select, TableOrSubquery,
+};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, tag_no_case, take_while, take_while1},
-    character::complete::{multispace0, multispace1},
-    combinator::opt,
-    multi::{separated_list0, separated_list1},
-    sequence::{delimited, preceded},
-    IResult, Parser,
+    bytes::complete::{tag, tag_no_case, take_while1},
+    character::complete::{char, i64, multispace0, multispace1, satisfy},
+    combinator::{cut, not, opt, recognize, value},
+    error::{Error, ErrorKind},
+    multi::{many0, many1, separated_list0, separated_list1},
+    number::complete::recognize_float,
+    sequence::{delimited, preceded, terminated},
+    Err, IResult, Parser,
 };
 
 type StrParser = dyn Fn(&str) -> IResult<&str, &str>;
 
 type TableName<'a> = &'a str;
-type ColName<'a> = &'a str;
-type ColDef<'a> = &'a str;
-type Condition<'a> = (ColName<'a>, &'a str);
 
-pub fn parse_select(
-    input: &str,
-) -> IResult<&str, (Vec<ColName<'_>>, TableName<'_>, Vec<Condition<'_>>)> {
+pub fn parse_select(input: &str) -> IResult<&str, Select> {
+    let (remaining, with) = opt(parse_with).parse(input)?;
+    let (remaining, select) = parse_select_core(remaining)?;
+    let (remaining, compound) = many0(
+        (parse_compound_operator, cut(parse_select_core))
+            .map(|(op, select)| Compound { op, select }),
+    )
+    .parse(remaining)?;
+    let (remaining, order_by) = opt(preceded(
+        (parse_word("order"), parse_word("by")),
+        cut(separated_list1(trim(tag(",")), parse_ordering_term)),
+    ))
+    .parse(remaining)?;
+    let (remaining, limit) = opt(parse_limit).parse(remaining)?;
+    Ok((
+        remaining,
+        Select {
+            with: with.unwrap_or_default(),
+            compound,
+            order_by: order_by.unwrap_or_default(),
+            limit: limit.unwrap_or_default(),
+            ..select
+        },
+    ))
+}
+
+/// Parses `SELECT` up to the clauses that apply to a whole compound select.
+fn parse_select_core(input: &str) -> IResult<&str, Select> {
     let (remaining, columns) = delimited(
         parse_keyword("select"),
-        parse_comma_separated_col_or_funcs,
+        parse_result_columns,
         parse_keyword("from"),
     )
     .parse(input)?;
-    let (remaining, table) = parse_table_name(remaining)?;
+    let (remaining, from) = parse_from(remaining)?;
+    // Once a clause has started, failing to parse it fails the statement
+    // there rather than leaving the clause as unexpected input.
+    let (remaining, r#where) =
+        opt(preceded(parse_keyword("where"), cut(parse_expr))).parse(remaining)?;
+    let (remaining, group_by) = opt(preceded(
+        (parse_word("group"), parse_word("by")),
+        cut(separated_list1(trim(tag(",")), parse_expr)),
+    ))
+    .parse(remaining)?;
+    let (remaining, having) =
+        opt(preceded(parse_word("having"), cut(parse_expr))).parse(remaining)?;
+    Ok((
+        remaining,
+        Select {
+            with: vec![],
+            columns,
+            from,
+            conditions: Conditions(r#where),
+            group_by: group_by.unwrap_or_default(),
+            having,
+            compound: vec![],
+            order_by: vec![],
+            limit: Limit::default(),
+        },
+    ))
+}
 
-    let (remaining, r#where) = opt(parse_keyword("where")).parse(remaining)?;
-    if r#where.is_none() {
-        Ok((remaining, (columns, table, vec![])))
-    } else {
-        let (remaining, conditions) = parse_and_conditions(remaining)?;
-        Ok((remaining, (columns, table, conditions)))
-    }
+/// Parses `UNION [ALL]`, `INTERSECT` or `EXCEPT`.
+fn parse_compound_operator(input: &str) -> IResult<&str, CompoundOp> {
+    alt((
+        value(
+            CompoundOp::UnionAll,
+            (parse_word("union"), parse_word("all")),
+        ),
+        value(CompoundOp::Union, parse_word("union")),
+        value(CompoundOp::Intersect, parse_word("intersect")),
+        value(CompoundOp::Except, parse_word("except")),
+    ))
+    .parse(input)
 }
 
-pub fn parse_create_table(input: &str) -> IResult<&str, (Vec<ColDef<'_>>, TableName<'_>)> {
-    let (remaining, _) = parse_keyword("create").parse(input)?;
-    let (remaining, _) = parse_keyword("table").parse(remaining)?;
+pub fn parse_insert(input: &str) -> IResult<&str, Insert<'_>> {
+    let (remaining, _) = (parse_keyword("insert"), parse_keyword("into")).parse(input)?;
     let (remaining, table) = parse_table_name(remaining)?;
-    let (remaining, col_defs) = delimited(
+    let (remaining, columns) = opt(delimited(
         trim(tag("(")),
-        parse_comma_separated_col_defs,
+        separated_list1(trim(tag(",")), parse_identifier),
         trim(tag(")")),
+    ))
+    .parse(remaining)?;
+    let (remaining, rows) = preceded(
+        parse_word("values"),
+        separated_list1(
+            trim(tag(",")),
+            delimited(
+                trim(tag("(")),
+                separated_list1(trim(tag(",")), parse_expr),
+                trim(tag(")")),
+            ),
+        ),
     )
     .parse(remaining)?;
-    Ok((remaining, (col_defs, table)))
+
+    Ok((
+        remaining,
+        Insert {
+            table,
+            columns: columns.unwrap_or_default(),
+            rows,
+        },
+    ))
+}
+
+/// Parses an expression with SQLite's operator precedence, from the loosest
+/// binding `OR` down to literals, column names and parenthesized expressions.
+pub fn parse_expr(input: &str) -> IResult<&str, Expr> {
+    parse_or(input)
+}
+
+/// Parses `CREATE [TEMP] TABLE [IF NOT EXISTS] [schema.]name (...)` followed
+/// by the `WITHOUT ROWID` and `STRICT` options.
+pub fn parse_create_table(input: &str) -> IResult<&str, TableDefinition> {
+    let (remaining, _) = (
+        parse_word("create"),
+        opt(alt((parse_word("temporary"), parse_word("temp")))),
+        parse_word("table"),
+        opt((parse_word("if"), parse_word("not"), parse_word("exists"))),
+    )
+        .parse(input)?;
+    let (remaining, name) = preceded(
+        opt(terminated(
+            preceded(multispace0, parse_name),
+            trim(tag(".")),
+        )),
+        preceded(multispace0, parse_name),
+    )
+    .parse(remaining)?;
+    let (remaining, (columns, constraints)) = delimited(
+        trim(tag("(")),
+        (
+            separated_list1(trim(tag(",")), parse_column_definition),
+            many0(preceded(opt(trim(tag(","))), parse_table_constraint)),
+        ),
+        trim(tag(")")),
+    )
+    .parse(remaining)?;
+    let (remaining, options) = separated_list0(
+        trim(tag(",")),
+        alt((
+            value(true, (parse_word("without"), parse_word("rowid"))),
+            value(false, parse_word("strict")),
+        )),
+    )
+    .parse(remaining)?;
+
+    Ok((
+        remaining,
+        TableDefinition {
+            name,
+            columns,
+            constraints,
+            without_rowid: options.contains(&true),
+            strict: options.contains(&false),
+        },
+    ))
 }
 
-pub fn parse_create_index(input: &str) -> IResult<&str, (Vec<ColName<'_>>, TableName<'_>)> {
+pub fn parse_create_view(input: &str) -> IResult<&str, ViewDefinition> {
+    let (remaining, _) = (
+        parse_word("create"),
+        opt(alt((parse_word("temporary"), parse_word("temp")))),
+        parse_word("view"),
+        opt((parse_word("if"), parse_word("not"), parse_word("exists"))),
+    )
+        .parse(input)?;
+    let (remaining, name) = preceded(
+        opt(terminated(
+            preceded(multispace0, parse_name),
+            trim(tag(".")),
+        )),
+        preceded(multispace0, parse_name),
+    )
+    .parse(remaining)?;
+    let (remaining, columns) = opt(parse_column_list).parse(remaining)?;
+    let (remaining, select) = preceded(parse_word("as"), parse_select).parse(remaining)?;
+
+    Ok((
+        remaining,
+        ViewDefinition {
+            name,
+            columns: columns.unwrap_or_default(),
+            select,
+        },
+    ))
+}
+
+pub fn parse_create_index(input: &str) -> IResult<&str, (Vec<IndexedColumn>, TableName<'_>)> {
     let (remaining, _) = parse_keyword("create").parse(input)?;
     let (remaining, _) = parse_keyword("index").parse(remaining)?;
     let (remaining, idx_name) = parse_table_name(remaining)?;
     let (remaining, _) = parse_keyword("on").parse(remaining)?;
     let (remaining, _table) = parse_table_name(remaining)?;
-    let (remaining, cols) =
-        delimited(trim(tag("(")), parse_comma_separated_cols, trim(tag(")"))).parse(remaining)?;
+    let (remaining, cols) = parse_indexed_columns(remaining)?;
     Ok((remaining, (cols, idx_name)))
 }
 
@@ -65,71 +231,746 @@ fn parse_keyword(keyword: &'static str) -> Box<StrParser> {
     })
 }
 
-fn parse_comma_separated_col_or_funcs(input: &str) -> IResult<&str, Vec<&str>> {
-    separated_list1(trim(tag(",")), trim(parse_col_or_funcs)).parse(input)
+/// Like `parse_keyword` but only requires that no identifier character
+/// follows, so that the keyword can be directly followed by a parenthesis.
+fn parse_word(keyword: &'static str) -> Box<StrParser> {
+    Box::new(move |input: &str| {
+        preceded(
+            multispace0,
+            terminated(tag_no_case(keyword), not(satisfy(is_identifier_chars))),
+        )
+        .parse(input)
+    })
 }
 
-fn parse_comma_separated_cols(input: &str) -> IResult<&str, Vec<&str>> {
-    separated_list1(trim(tag(",")), trim(parse_cols)).parse(input)
+fn parse_result_columns(input: &str) -> IResult<&str, Vec<ResultColumn>> {
+    separated_list1(trim(tag(",")), parse_result_column).parse(input)
 }
 
-fn parse_comma_separated_col_defs(input: &str) -> IResult<&str, Vec<ColDef<'_>>> {
-    separated_list1(trim(tag(",")), parse_col_defs).parse(input)
+fn parse_result_column(input: &str) -> IResult<&str, ResultColumn> {
+    alt((
+        value(ResultColumn::All, trim(tag("*"))),
+        terminated(
+            preceded(multispace0, parse_identifier),
+            (trim(tag(".")), tag("*"), multispace0),
+        )
+        .map(ResultColumn::TableAll),
+        (parse_expr, opt(parse_alias)).map(|(expr, alias)| ResultColumn::Expr { expr, alias }),
+    ))
+    .parse(input)
 }
 
-fn parse_and_conditions(input: &str) -> IResult<&str, Vec<Condition<'_>>> {
-    separated_list0(trim(tag_no_case("and")), parse_eq_condition).parse(input)
+/// Parses `[AS] alias`. Without `AS`, the alias cannot be a keyword that may
+/// follow a result column or a table.
+fn parse_alias(input: &str) -> IResult<&str, String> {
+    alt((
+        preceded(parse_word("as"), cut(preceded(multispace0, parse_name))),
+        preceded(
+            not(alt((
+                alt((
+                    parse_word("from"),
+                    parse_word("where"),
+                    parse_word("group"),
+                    parse_word("having"),
+                    parse_word("order"),
+                    parse_word("limit"),
+                    parse_word("union"),
+                    parse_word("intersect"),
+                    parse_word("except"),
+                )),
+                alt((
+                    parse_word("on"),
+                    parse_word("using"),
+                    parse_word("join"),
+                    parse_word("inner"),
+                    parse_word("cross"),
+                    parse_word("left"),
+                    parse_word("natural"),
+                )),
+            ))),
+            preceded(multispace0, parse_name),
+        ),
+    ))
+    .parse(input)
 }
 
-fn trim<'a>(
-    f: impl Parser<&'a str, Output = &'a str, Error = nom::error::Error<&'a str>>,
-) -> impl Parser<&'a str, Output = &'a str, Error = nom::error::Error<&'a str>> {
-    delimited(multispace0, f, multispace0)
+/// Parses the tables of a `FROM` clause, each after the first one following
+/// a join operator and followed by an optional `ON` or `USING` constraint.
+fn parse_from(input: &str) -> IResult<&str, Vec<FromTable>> {
+    let (mut remaining, (table, alias)) = parse_table_alias(input)?;
+    let mut from = vec![FromTable {
+        table,
+        alias,
+        join: JoinKind::Inner,
+        constraint: JoinConstraint::None,
+    }];
+
+    while let Ok((rest, join)) = parse_join_operator(remaining) {
+        let (rest, (table, alias)) = cut(parse_table_alias).parse(rest)?;
+        let (rest, constraint) = opt(alt((
+            preceded(parse_word("on"), cut(parse_expr)).map(JoinConstraint::On),
+            preceded(parse_word("using"), cut(parse_column_list)).map(JoinConstraint::Using),
+        )))
+        .parse(rest)?;
+
+        from.push(FromTable {
+            table,
+            alias,
+            join,
+            constraint: constraint.unwrap_or(JoinConstraint::None),
+        });
+        remaining = rest;
+    }
+
+    Ok((remaining, from))
+}
+
+/// Parses `[schema.]table [[AS] alias]` or `(select) [[AS] alias]`.
+fn parse_table_alias(input: &str) -> IResult<&str, (TableOrSubquery, Option<String>)> {
+    (
+        alt((
+            parse_subquery.map(|select| TableOrSubquery::Subquery(Box::new(select))),
+            preceded(
+                opt(terminated(
+                    preceded(multispace0, parse_name),
+                    trim(tag(".")),
+                )),
+                preceded(multispace0, parse_name),
+            )
+            .map(TableOrSubquery::Table),
+        )),
+        opt(parse_alias),
+    )
+        .parse(input)
 }
 
-fn parse_col_defs(input: &str) -> IResult<&str, ColDef<'_>> {
-    let (remaining, col_def) =
-        preceded(multispace0, take_while(is_line_with_quotes)).parse(input)?;
-    Ok((remaining, col_def))
+/// Parses a `SELECT` statement between parentheses.
+fn parse_subquery(input: &str) -> IResult<&str, Select> {
+    delimited(trim(tag("(")), parse_select, trim(tag(")"))).parse(input)
 }
 
-fn parse_eq_condition(input: &str) -> IResult<&str, Condition<'_>> {
-    let (remaining, col_name) = preceded(multispace0, parse_col_name_and_def).parse(input)?;
-    let (remaining, _) = trim(tag("=")).parse(remaining)?;
-    let (remaining, value) = preceded(multispace0, parse_any_value).parse(remaining)?;
+/// Parses `WITH [RECURSIVE]` and the common table expressions that follow.
+fn parse_with(input: &str) -> IResult<&str, Vec<CommonTableExpr>> {
+    preceded(
+        (parse_word("with"), opt(parse_word("recursive"))),
+        cut(separated_list1(trim(tag(",")), parse_common_table_expr)),
+    )
+    .parse(input)
+}
+
+/// Parses `name [(columns)] AS [[NOT] MATERIALIZED] (select)`.
+fn parse_common_table_expr(input: &str) -> IResult<&str, CommonTableExpr> {
+    let (remaining, name) = preceded(multispace0, parse_name).parse(input)?;
+    let (remaining, columns) = opt(parse_column_list).parse(remaining)?;
+    let (remaining, _) = (
+        parse_word("as"),
+        opt((opt(parse_word("not")), parse_word("materialized"))),
+    )
+        .parse(remaining)?;
+    let (remaining, select) = parse_subquery(remaining)?;
+
     Ok((
         remaining,
-        (col_name, value.trim_matches('\'').trim_matches('"')),
+        CommonTableExpr {
+            name,
+            columns: columns.unwrap_or_default(),
+            select,
+        },
     ))
 }
 
-fn parse_cols(input: &str) -> IResult<&str, &str> {
+/// Parses `,`, `[INNER | CROSS] JOIN` or `LEFT [OUTER] JOIN`.
+fn parse_join_operator(input: &str) -> IResult<&str, JoinKind> {
     alt((
-        take_while1(is_identifier_chars),
-        parse_double_quoted,
-        parse_single_quoted,
+        value(JoinKind::Inner, trim(tag(","))),
+        value(
+            JoinKind::Left,
+            (
+                parse_word("left"),
+                opt(parse_word("outer")),
+                parse_word("join"),
+            ),
+        ),
+        value(
+            JoinKind::Inner,
+            (
+                opt(alt((parse_word("inner"), parse_word("cross")))),
+                parse_word("join"),
+            ),
+        ),
     ))
     .parse(input)
 }
 
-fn parse_col_or_funcs(input: &str) -> IResult<&str, &str> {
-    take_while1(is_identifier_or_fn_chars).parse(input)
+fn parse_ordering_term(input: &str) -> IResult<&str, OrderingTerm> {
+    let (remaining, (expr, desc, nulls_first)) = (
+        parse_expr,
+        opt(parse_sort_order),
+        opt(preceded(
+            parse_word("nulls"),
+            alt((
+                value(true, parse_word("first")),
+                value(false, parse_word("last")),
+            )),
+        )),
+    )
+        .parse(input)?;
+
+    let desc = desc.unwrap_or(false);
+    Ok((
+        remaining,
+        OrderingTerm {
+            expr,
+            desc,
+            // NULL is the smallest value unless told otherwise.
+            nulls_first: nulls_first.unwrap_or(!desc),
+        },
+    ))
 }
 
-fn parse_table_name(input: &str) -> IResult<&str, &str> {
+/// Parses `LIMIT count [OFFSET offset]` and its `LIMIT offset, count` form.
+fn parse_limit(input: &str) -> IResult<&str, Limit> {
+    let (remaining, _) = parse_word("limit")(input)?;
+    let (remaining, first) = preceded(multispace0, i64).parse(remaining)?;
+    let (remaining, second) = opt(alt((
+        preceded(parse_word("offset"), preceded(multispace0, i64)).map(|n| (first, n)),
+        preceded(trim(tag(",")), i64).map(|n| (n, first)),
+    )))
+    .parse(remaining)?;
+
+    let (count, offset) = second.unwrap_or((first, 0));
+    Ok((
+        remaining,
+        Limit {
+            // A negative count means no limit.
+            count: usize::try_from(count).ok(),
+            offset: usize::try_from(offset).unwrap_or(0),
+        },
+    ))
+}
+
+fn parse_or(input: &str) -> IResult<&str, Expr> {
+    let (mut remaining, mut expr) = parse_and(input)?;
+
+    while let Ok((rest, _)) = parse_word("or")(remaining) {
+        let (rest, right) = parse_and(rest)?;
+        expr = Expr::binary(expr, BinaryOp::Or, right);
+        remaining = rest;
+    }
+
+    Ok((remaining, expr))
+}
+
+fn parse_and(input: &str) -> IResult<&str, Expr> {
+    let (mut remaining, mut expr) = parse_not(input)?;
+
+    while let Ok((rest, _)) = parse_word("and")(remaining) {
+        let (rest, right) = parse_not(rest)?;
+        expr = Expr::binary(expr, BinaryOp::And, right);
+        remaining = rest;
+    }
+
+    Ok((remaining, expr))
+}
+
+fn parse_not(input: &str) -> IResult<&str, Expr> {
+    match parse_word("not")(input) {
+        Ok((remaining, _)) => {
+            let (remaining, expr) = parse_not(remaining)?;
+            Ok((remaining, Expr::not(expr)))
+        }
+        Err(_) => parse_equality(input),
+    }
+}
+
+/// Parses the operators sharing the precedence of `=`: `==`, `!=`, `<>`,
+/// `IS [NOT]`, `[NOT] IN`, `[NOT] BETWEEN`, `[NOT] LIKE`, `[NOT] GLOB` and the
+/// postfix `ISNULL`, `NOTNULL` and `NOT NULL`.
+fn parse_equality(input: &str) -> IResult<&str, Expr> {
+    let (mut remaining, mut expr) = parse_comparison(input)?;
+
+    loop {
+        if let Ok((rest, op)) = parse_equality_op(remaining) {
+            let (rest, right) = parse_comparison(rest)?;
+            expr = Expr::binary(expr, op, right);
+            remaining = rest;
+        } else if let Ok((rest, (negated, postfix))) = parse_postfix(remaining) {
+            expr = match postfix {
+                Postfix::IsNull => Expr::binary(expr, BinaryOp::Is, Expr::Literal(Literal::Null)),
+                Postfix::In(list) => Expr::In {
+                    expr: Box::new(expr),
+                    list,
+                },
+                Postfix::InSelect(select) => Expr::InSelect {
+                    expr: Box::new(expr),
+                    select: Box::new(select),
+                },
+                Postfix::Between(low, high) => Expr::Between {
+                    expr: Box::new(expr),
+                    low: Box::new(low),
+                    high: Box::new(high),
+                },
+                Postfix::Pattern(op, pattern) => Expr::binary(expr, op, pattern),
+            };
+            if negated {
+                expr = Expr::not(expr);
+            }
+            remaining = rest;
+        } else {
+            return Ok((remaining, expr));
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Postfix {
+    IsNull,
+    In(Vec<Expr>),
+    InSelect(Select),
+    Between(Expr, Expr),
+    Pattern(BinaryOp, Expr),
+}
+
+/// Parses the operators following their left operand, which can be negated
+/// with a `NOT` in between.
+fn parse_postfix(input: &str) -> IResult<&str, (bool, Postfix)> {
+    if let Ok((remaining, _)) = parse_word("isnull")(input) {
+        return Ok((remaining, (false, Postfix::IsNull)));
+    }
+    if let Ok((remaining, _)) = parse_word("notnull")(input) {
+        return Ok((remaining, (true, Postfix::IsNull)));
+    }
+
+    let (remaining, negated) = opt(parse_word("not")).parse(input)?;
+    let (remaining, postfix) = alt((
+        value(Postfix::IsNull, parse_word("null")),
+        preceded(parse_word("in"), parse_subquery).map(Postfix::InSelect),
+        preceded(
+            parse_word("in"),
+            delimited(
+                trim(tag("(")),
+                separated_list0(trim(tag(",")), parse_expr),
+                trim(tag(")")),
+            ),
+        )
+        .map(Postfix::In),
+        (
+            preceded(parse_word("between"), parse_comparison),
+            preceded(parse_word("and"), parse_comparison),
+        )
+            .map(|(low, high)| Postfix::Between(low, high)),
+        (
+            alt((
+                value(BinaryOp::Like, parse_word("like")),
+                value(BinaryOp::Glob, parse_word("glob")),
+            )),
+            parse_comparison,
+        )
+            .map(|(op, pattern)| Postfix::Pattern(op, pattern)),
+    ))
+    .parse(remaining)?;
+
+    Ok((remaining, (negated.is_some(), postfix)))
+}
+
+fn parse_equality_op(input: &str) -> IResult<&str, BinaryOp> {
     alt((
-        take_while1(is_identifier_chars),
-        parse_double_quoted,
-        parse_single_quoted,
+        value(BinaryOp::Eq, trim(tag("=="))),
+        value(BinaryOp::Eq, trim(tag("="))),
+        value(BinaryOp::Ne, trim(tag("!="))),
+        value(BinaryOp::Ne, trim(tag("<>"))),
+        value(BinaryOp::IsNot, (parse_word("is"), parse_word("not"))),
+        value(BinaryOp::Is, parse_word("is")),
+    ))
+    .parse(input)
+}
+
+fn parse_comparison(input: &str) -> IResult<&str, Expr> {
+    let (mut remaining, mut expr) = parse_primary(input)?;
+
+    while let Ok((rest, op)) = parse_comparison_op(remaining) {
+        let (rest, right) = parse_primary(rest)?;
+        expr = Expr::binary(expr, op, right);
+        remaining = rest;
+    }
+
+    Ok((remaining, expr))
+}
+
+fn parse_comparison_op(input: &str) -> IResult<&str, BinaryOp> {
+    alt((
+        value(BinaryOp::Le, trim(tag("<="))),
+        value(BinaryOp::Ge, trim(tag(">="))),
+        value(BinaryOp::Lt, trim(terminated(tag("<"), not(char('>'))))),
+        value(BinaryOp::Gt, trim(tag(">"))),
+    ))
+    .parse(input)
+}
+
+fn parse_primary(input: &str) -> IResult<&str, Expr> {
+    preceded(
+        multispace0,
+        alt((
+            parse_subquery.map(|select| Expr::Subquery(Box::new(select))),
+            delimited(tag("("), parse_expr, trim(tag(")"))),
+            parse_literal.map(Expr::Literal),
+            preceded(parse_word("exists"), parse_subquery)
+                .map(|select| Expr::Exists(Box::new(select))),
+            parse_function,
+            (parse_identifier, preceded(trim(tag(".")), parse_identifier)).map(|(table, name)| {
+                Expr::Column {
+                    table: Some(table),
+                    name,
+                }
+            }),
+            parse_identifier.map(Expr::column),
+        )),
+    )
+    .parse(input)
+}
+
+/// Parses `name([DISTINCT] args)`, where `count(*)` has no arguments.
+fn parse_function(input: &str) -> IResult<&str, Expr> {
+    let (remaining, name) =
+        terminated(take_while1(is_identifier_chars), trim(tag("("))).parse(input)?;
+    let (remaining, (distinct, args)) = alt((
+        value((false, vec![]), trim(tag("*"))),
+        (
+            opt(parse_word("distinct")).map(|d| d.is_some()),
+            separated_list0(trim(tag(",")), parse_expr),
+        ),
+    ))
+    .parse(remaining)?;
+    let (remaining, _) = trim(tag(")")).parse(remaining)?;
+
+    Ok((
+        remaining,
+        Expr::Function {
+            name: name.to_lowercase(),
+            args,
+            distinct,
+        },
+    ))
+}
+
+fn parse_literal(input: &str) -> IResult<&str, Literal> {
+    alt((
+        value(Literal::Null, parse_word("null")),
+        parse_quoted('\'', '\'').map(Literal::Text),
+        parse_number,
+    ))
+    .parse(input)
+}
+
+fn parse_number(input: &str) -> IResult<&str, Literal> {
+    let (remaining, num) = recognize_float(input)?;
+
+    let literal = match num.parse::<i64>() {
+        Ok(n) => Literal::Integer(n),
+        Err(_) => match num.parse::<f64>() {
+            Ok(n) => Literal::Float(n),
+            Err(_) => return Err(Err::Error(Error::new(input, ErrorKind::Float))),
+        },
+    };
+    Ok((remaining, literal))
+}
+
+/// Parses a bare identifier or one quoted with double quotes, backticks or
+/// square brackets.
+fn parse_identifier(input: &str) -> IResult<&str, String> {
+    alt((
+        take_while1(is_identifier_chars).map(String::from),
+        parse_quoted('"', '"'),
+        parse_quoted('`', '`'),
+        parse_quoted('[', ']'),
+    ))
+    .parse(input)
+}
+
+/// Parses text between `open` and `close` where a doubled closing character
+/// stands for the character itself.
+fn parse_quoted(open: char, close: char) -> impl Fn(&str) -> IResult<&str, String> {
+    move |input: &str| {
+        let (mut remaining, _) = char(open)(input)?;
+        let mut quoted = String::new();
+
+        loop {
+            let end = remaining
+                .find(close)
+                .ok_or(Err::Error(Error::new(remaining, ErrorKind::Char)))?;
+            quoted.push_str(&remaining[..end]);
+            remaining = &remaining[end + close.len_utf8()..];
+
+            if open == close && remaining.starts_with(close) {
+                quoted.push(close);
+                remaining = &remaining[close.len_utf8()..];
+            } else {
+                return Ok((remaining, quoted));
+            }
+        }
+    }
+}
+
+/// Parses `name [type] [constraints]`. A name starting a table constraint
+/// ends the column definitions.
+fn parse_column_definition(input: &str) -> IResult<&str, ColumnDefinition> {
+    let (remaining, _) = not(alt((
+        parse_word("constraint"),
+        parse_word("primary"),
+        parse_word("unique"),
+        parse_word("check"),
+        parse_word("foreign"),
+    )))
+    .parse(input)?;
+    let (remaining, name) = preceded(multispace0, parse_name).parse(remaining)?;
+    let (remaining, r#type) = opt(parse_type_name).parse(remaining)?;
+    let (remaining, constraints) = many0(parse_column_constraint).parse(remaining)?;
+
+    Ok((
+        remaining,
+        ColumnDefinition {
+            name,
+            r#type: r#type.unwrap_or_default().to_string(),
+            constraints: constraints.into_iter().flatten().collect(),
+        },
+    ))
+}
+
+/// Parses type names like `INTEGER`, `UNSIGNED BIG INT` or `VARCHAR(255)`,
+/// whose words end at the first constraint keyword.
+fn parse_type_name(input: &str) -> IResult<&str, &str> {
+    let word = preceded(
+        not(alt((
+            parse_word("constraint"),
+            parse_word("primary"),
+            parse_word("not"),
+            parse_word("null"),
+            parse_word("unique"),
+            parse_word("check"),
+            parse_word("default"),
+            parse_word("collate"),
+            parse_word("references"),
+            parse_word("generated"),
+            parse_word("as"),
+        ))),
+        preceded(multispace0, take_while1(is_identifier_chars)),
+    );
+    let size = delimited(
+        trim(tag("(")),
+        separated_list1(trim(tag(",")), recognize_float),
+        tag(")"),
+    );
+
+    let (remaining, _) = multispace0(input)?;
+    recognize((many1(word), opt(size))).parse(remaining)
+}
+
+/// Parses a column constraint, or a `NULL` constraint which allows what is
+/// allowed anyway.
+fn parse_column_constraint(input: &str) -> IResult<&str, Option<ColumnConstraint>> {
+    let (remaining, _) = opt(parse_constraint_name).parse(input)?;
+    alt((
+        (
+            parse_word("primary"),
+            parse_word("key"),
+            opt(parse_sort_order),
+            parse_conflict_clause,
+            opt(parse_word("autoincrement")),
+        )
+            .map(|(_, _, desc, _, autoincrement)| {
+                Some(ColumnConstraint::PrimaryKey {
+                    desc: desc.unwrap_or(false),
+                    autoincrement: autoincrement.is_some(),
+                })
+            }),
+        (parse_word("not"), parse_word("null"), parse_conflict_clause)
+            .map(|_| Some(ColumnConstraint::NotNull)),
+        (parse_word("null"), parse_conflict_clause).map(|_| None),
+        (parse_word("unique"), parse_conflict_clause).map(|_| Some(ColumnConstraint::Unique)),
+        preceded(parse_word("check"), parse_parenthesized_expr)
+            .map(|expr| Some(ColumnConstraint::Check(expr))),
+        preceded(parse_word("default"), parse_primary)
+            .map(|expr| Some(ColumnConstraint::Default(expr))),
+        preceded(parse_word("collate"), preceded(multispace0, parse_name))
+            .map(|name| Some(ColumnConstraint::Collate(name))),
+        parse_foreign_key_clause.map(|fk| Some(ColumnConstraint::References(fk))),
+        (
+            opt((parse_word("generated"), parse_word("always"))),
+            parse_word("as"),
+            parse_parenthesized_expr,
+            opt(alt((
+                value(true, parse_word("stored")),
+                value(false, parse_word("virtual")),
+            ))),
+        )
+            .map(|(_, _, expr, stored)| {
+                Some(ColumnConstraint::Generated {
+                    expr,
+                    stored: stored.unwrap_or(false),
+                })
+            }),
+    ))
+    .parse(remaining)
+}
+
+fn parse_table_constraint(input: &str) -> IResult<&str, TableConstraint> {
+    let (remaining, _) = opt(parse_constraint_name).parse(input)?;
+    alt((
+        delimited(
+            (parse_word("primary"), parse_word("key")),
+            parse_indexed_columns,
+            parse_conflict_clause,
+        )
+        .map(TableConstraint::PrimaryKey),
+        delimited(
+            parse_word("unique"),
+            parse_indexed_columns,
+            parse_conflict_clause,
+        )
+        .map(TableConstraint::Unique),
+        preceded(parse_word("check"), parse_parenthesized_expr).map(TableConstraint::Check),
+        (
+            preceded(
+                (parse_word("foreign"), parse_word("key")),
+                parse_column_list,
+            ),
+            parse_foreign_key_clause,
+        )
+            .map(|(columns, references)| TableConstraint::ForeignKey {
+                columns,
+                references,
+            }),
+    ))
+    .parse(remaining)
+}
+
+fn parse_constraint_name(input: &str) -> IResult<&str, String> {
+    preceded(parse_word("constraint"), preceded(multispace0, parse_name)).parse(input)
+}
+
+/// Parses `ON CONFLICT` with its algorithm, which is not kept.
+fn parse_conflict_clause(input: &str) -> IResult<&str, ()> {
+    let algorithm = alt((
+        parse_word("rollback"),
+        parse_word("abort"),
+        parse_word("fail"),
+        parse_word("ignore"),
+        parse_word("replace"),
+    ));
+    value(
+        (),
+        opt((parse_word("on"), parse_word("conflict"), algorithm)),
+    )
+    .parse(input)
+}
+
+/// Parses `REFERENCES table [(columns)]` followed by the actions, match type
+/// and deferral of the foreign key, which are not kept.
+fn parse_foreign_key_clause(input: &str) -> IResult<&str, ForeignKey> {
+    let action = alt((
+        value((), (parse_word("set"), parse_word("null"))),
+        value((), (parse_word("set"), parse_word("default"))),
+        value((), parse_word("cascade")),
+        value((), parse_word("restrict")),
+        value((), (parse_word("no"), parse_word("action"))),
+    ));
+    let rule = alt((
+        value(
+            (),
+            (
+                parse_word("on"),
+                alt((parse_word("delete"), parse_word("update"))),
+                action,
+            ),
+        ),
+        value((), (parse_word("match"), preceded(multispace0, parse_name))),
+    ));
+    let deferrable = (
+        opt(parse_word("not")),
+        parse_word("deferrable"),
+        opt((
+            parse_word("initially"),
+            alt((parse_word("deferred"), parse_word("immediate"))),
+        )),
+    );
+
+    let (remaining, (table, columns, _, _)) = (
+        preceded(parse_word("references"), preceded(multispace0, parse_name)),
+        opt(parse_column_list),
+        many0(rule),
+        opt(deferrable),
+    )
+        .parse(input)?;
+
+    Ok((
+        remaining,
+        ForeignKey {
+            table,
+            columns: columns.unwrap_or_default(),
+        },
+    ))
+}
+
+/// Parses `(column [COLLATE name] [ASC | DESC], ...)`.
+fn parse_indexed_columns(input: &str) -> IResult<&str, Vec<IndexedColumn>> {
+    delimited(
+        trim(tag("(")),
+        separated_list1(
+            trim(tag(",")),
+            (
+                preceded(multispace0, parse_name),
+                opt(preceded(
+                    parse_word("collate"),
+                    preceded(multispace0, parse_name),
+                )),
+                opt(parse_sort_order),
+            )
+                .map(|(name, collation, desc)| IndexedColumn {
+                    name,
+                    collation,
+                    desc: desc.unwrap_or(false),
+                }),
+        ),
+        trim(tag(")")),
+    )
+    .parse(input)
+}
+
+fn parse_column_list(input: &str) -> IResult<&str, Vec<String>> {
+    delimited(
+        trim(tag("(")),
+        separated_list1(trim(tag(",")), preceded(multispace0, parse_name)),
+        trim(tag(")")),
+    )
+    .parse(input)
+}
+
+fn parse_parenthesized_expr(input: &str) -> IResult<&str, Expr> {
+    delimited(trim(tag("(")), parse_expr, trim(tag(")"))).parse(input)
+}
+
+/// Parses `ASC` or `DESC`, telling whether the order is descending.
+fn parse_sort_order(input: &str) -> IResult<&str, bool> {
+    alt((
+        value(false, parse_word("asc")),
+        value(true, parse_word("desc")),
     ))
     .parse(input)
 }
 
-fn parse_col_name_and_def(input: &str) -> IResult<&str, &str> {
-    take_while1(is_identifier_chars).parse(input)
+/// Parses the name of a schema object, which may also be quoted like a
+/// string.
+fn parse_name(input: &str) -> IResult<&str, String> {
+    alt((parse_identifier, parse_quoted('\'', '\''))).parse(input)
+}
+
+fn trim<'a>(
+    f: impl Parser<&'a str, Output = &'a str, Error = nom::error::Error<&'a str>>,
+) -> impl Parser<&'a str, Output = &'a str, Error = nom::error::Error<&'a str>> {
+    delimited(multispace0, f, multispace0)
 }
 
-fn parse_any_value(input: &str) -> IResult<&str, &str> {
+fn parse_table_name(input: &str) -> IResult<&str, &str> {
     alt((
         take_while1(is_identifier_chars),
         parse_double_quoted,
@@ -156,10 +997,6 @@ fn parse_single_quoted(input: &str) -> IResult<&str, &str> {
     .parse(input)
 }
 
-fn is_identifier_or_fn_chars(c: char) -> bool {
-    is_identifier_chars(c) || c == '*' || c == '(' || c == ')'
-}
-
 fn is_identifier_chars(c: char) -> bool {
     c.is_ascii_alphanumeric() || c == '_'
 }
@@ -168,10 +1005,6 @@ fn is_any_line_chars(c: char) -> bool {
     is_identifier_chars(c) || c.is_ascii_whitespace()
 }
 
-fn is_line_with_quotes(c: char) -> bool {
-    is_any_line_chars(c) || c == '\'' || c == '"'
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,37 +1017,201 @@ mod tests {
         let input = "CREATE INDEX idx_companies_country\non companies (country)";
         let (remaining, (columns, index)) = parse_create_index(input)?;
         assert_eq!(remaining, "");
-        assert_eq!(columns, vec!["country",]);
+        let names: Vec<&str> = columns.iter().map(|col| col.name.as_str()).collect();
+        assert_eq!(names, vec!["country"]);
+        assert!(columns[0].is_ascending_binary());
         assert_eq!(index, "idx_companies_country");
 
+        let input = "CREATE INDEX i ON t (a COLLATE NOCASE, b DESC)";
+        let (_, (columns, _)) = parse_create_index(input)?;
+        assert_eq!(columns[0].collation, Some("NOCASE".into()));
+        assert!(!columns[0].desc);
+        assert!(columns[1].desc);
+
         Ok(())
     }
 
     #[test]
-    fn it_parses_and_separated_conditions() -> TestResult {
-        let input = "foo = 'bar' and baz = \"foobarbaz\"";
-        let (remaining, parsed) = parse_and_conditions(input)?;
+    fn it_parses_create_view_sentences() -> TestResult {
+        let input = "CREATE TEMP VIEW IF NOT EXISTS main.\"red apples\" (id, n)\nAS SELECT id, name FROM apples \
+            UNION SELECT id, name FROM oranges";
+        let (remaining, view) = parse_create_view(input)?;
         assert_eq!(remaining, "");
-        assert_eq!(parsed, vec![("foo", "bar"), ("baz", "foobarbaz")]);
+        assert_eq!(view.name, "red apples");
+        assert_eq!(view.columns, vec!["id", "n"]);
+        assert_eq!(view.select.compound.len(), 1);
+
+        let (_, view) = parse_create_view("create view v as select * from t")?;
+        assert!(view.columns.is_empty());
+        assert_eq!(view.select.columns, vec![ResultColumn::All]);
         Ok(())
     }
 
+    fn col(name: &str) -> Expr {
+        Expr::column(name)
+    }
+
+    fn text(t: &str) -> Expr {
+        Expr::Literal(Literal::Text(t.into()))
+    }
+
+    fn int(n: i64) -> Expr {
+        Expr::Literal(Literal::Integer(n))
+    }
+
     #[test]
-    fn it_parses_eq_condition() -> TestResult {
+    fn it_parses_comparisons() -> TestResult {
         let input = "foo = 'bar'";
-        let (_, (col, val)) = parse_eq_condition(input)?;
-        assert_eq!(col, "foo");
-        assert_eq!(val, "bar");
+        let (remaining, expr) = parse_expr(input)?;
+        assert_eq!(remaining, "");
+        assert_eq!(expr, Expr::binary(col("foo"), BinaryOp::Eq, text("bar")));
+
+        let input = "\n\"size range\" <> 'it''s' limit 1";
+        let (remaining, expr) = parse_expr(input)?;
+        assert_eq!(remaining, " limit 1");
+        assert_eq!(
+            expr,
+            Expr::binary(col("size range"), BinaryOp::Ne, text("it's"))
+        );
 
-        let input = "\nfoo = bar and ...";
-        let (remaining, (col, val)) = parse_eq_condition(input)?;
-        assert_eq!(remaining, " and ...");
-        assert_eq!(col, "foo");
-        assert_eq!(val, "bar");
+        for (input, op) in [
+            ("a<1", BinaryOp::Lt),
+            ("a <= 1", BinaryOp::Le),
+            ("a>1", BinaryOp::Gt),
+            ("a >= 1", BinaryOp::Ge),
+            ("a != 1", BinaryOp::Ne),
+            ("a == 1", BinaryOp::Eq),
+            ("a is 1", BinaryOp::Is),
+            ("a IS NOT 1", BinaryOp::IsNot),
+        ] {
+            let (remaining, expr) = parse_expr(input)?;
+            assert_eq!(remaining, "");
+            assert_eq!(expr, Expr::binary(col("a"), op, int(1)));
+        }
 
         Ok(())
     }
 
+    #[test]
+    fn it_parses_literals() -> TestResult {
+        let (_, expr) = parse_expr("-12")?;
+        assert_eq!(expr, int(-12));
+
+        let (_, expr) = parse_expr("1.5e2")?;
+        assert_eq!(expr, Expr::Literal(Literal::Float(150.0)));
+
+        let (_, expr) = parse_expr("NULL")?;
+        assert_eq!(expr, Expr::Literal(Literal::Null));
+
+        let (_, expr) = parse_expr("nullable")?;
+        assert_eq!(expr, col("nullable"));
+
+        let (_, expr) = parse_expr("[a b] = `c`")?;
+        assert_eq!(expr, Expr::binary(col("a b"), BinaryOp::Eq, col("c")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_boolean_operators_by_precedence() -> TestResult {
+        let input = "a = 1 or not b = 2 and (c = 3 or d = 4)";
+        let (remaining, expr) = parse_expr(input)?;
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::binary(
+                Expr::binary(col("a"), BinaryOp::Eq, int(1)),
+                BinaryOp::Or,
+                Expr::binary(
+                    Expr::not(Expr::binary(col("b"), BinaryOp::Eq, int(2))),
+                    BinaryOp::And,
+                    Expr::binary(
+                        Expr::binary(col("c"), BinaryOp::Eq, int(3)),
+                        BinaryOp::Or,
+                        Expr::binary(col("d"), BinaryOp::Eq, int(4)),
+                    ),
+                ),
+            )
+        );
+
+        let input = "a < 1 = b > 2";
+        let (_, expr) = parse_expr(input)?;
+        assert_eq!(
+            expr,
+            Expr::binary(
+                Expr::binary(col("a"), BinaryOp::Lt, int(1)),
+                BinaryOp::Eq,
+                Expr::binary(col("b"), BinaryOp::Gt, int(2)),
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_postfix_operators() -> TestResult {
+        let null = || Expr::Literal(Literal::Null);
+
+        let (_, expr) = parse_expr("a IS NULL")?;
+        assert_eq!(expr, Expr::binary(col("a"), BinaryOp::Is, null()));
+
+        let (_, expr) = parse_expr("a not null")?;
+        assert_eq!(
+            expr,
+            Expr::not(Expr::binary(col("a"), BinaryOp::Is, null()))
+        );
+
+        let (_, expr) = parse_expr("a isnull")?;
+        assert_eq!(expr, Expr::binary(col("a"), BinaryOp::Is, null()));
+
+        let (remaining, expr) = parse_expr("a NOT IN (1, 'b') and c")?;
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::binary(
+                Expr::not(Expr::In {
+                    expr: Box::new(col("a")),
+                    list: vec![int(1), text("b")],
+                }),
+                BinaryOp::And,
+                col("c"),
+            )
+        );
+
+        let (remaining, expr) = parse_expr("a between 1 and 2 and b")?;
+        assert_eq!(remaining, "");
+        assert_eq!(
+            expr,
+            Expr::binary(
+                Expr::Between {
+                    expr: Box::new(col("a")),
+                    low: Box::new(int(1)),
+                    high: Box::new(int(2)),
+                },
+                BinaryOp::And,
+                col("b"),
+            )
+        );
+
+        let (_, expr) = parse_expr("a like 'x%'")?;
+        assert_eq!(expr, Expr::binary(col("a"), BinaryOp::Like, text("x%")));
+
+        let (_, expr) = parse_expr("a NOT GLOB 'x*'")?;
+        assert_eq!(
+            expr,
+            Expr::not(Expr::binary(col("a"), BinaryOp::Glob, text("x*")))
+        );
+
+        Ok(())
+    }
+
+    fn names(def: &TableDefinition) -> Vec<(&str, &str)> {
+        def.columns
+            .iter()
+            .map(|col| (col.name.as_str(), col.r#type.as_str()))
+            .collect()
+    }
+
     #[test]
     fn it_parses_create_table_sentences() -> TestResult {
         let input = "CREATE TABLE sqlite_schema(\n\
@@ -224,18 +1221,19 @@ mod tests {
                 rootpage integer,\n\
                 sql text\n\
             );";
-        let (_, (columns, table)) = parse_create_table(input)?;
+        let (remaining, def) = parse_create_table(input)?;
+        assert_eq!(remaining, ";");
+        assert_eq!(def.name, "sqlite_schema");
         assert_eq!(
-            columns,
+            names(&def),
             vec![
-                "type text",
-                "name text",
-                "tbl_name text",
-                "rootpage integer",
-                "sql text\n",
+                ("type", "text"),
+                ("name", "text"),
+                ("tbl_name", "text"),
+                ("rootpage", "integer"),
+                ("sql", "text"),
             ]
         );
-        assert_eq!(table, "sqlite_schema");
 
         let input = "CREATE TABLE oranges\n\
             (\n\
@@ -243,126 +1241,373 @@ mod tests {
                 name text,\n\
                 description text\n\
             );";
-        let (_, (columns, table)) = parse_create_table(input)?;
+        let (_, def) = parse_create_table(input)?;
+        assert_eq!(def.name, "oranges");
         assert_eq!(
-            columns,
+            def.columns[0].constraints,
+            vec![ColumnConstraint::PrimaryKey {
+                desc: false,
+                autoincrement: true,
+            }]
+        );
+        assert_eq!(def.rowid_alias(), Some(0));
+
+        let input = "CREATE TABLE companies\n\
+            (\n\
+                id integer primary key autoincrement, name text, \"size range\" text, [first name] TEXT, `last name`)";
+        let (_, def) = parse_create_table(input)?;
+        assert_eq!(
+            names(&def),
             vec![
-                "id integer primary key autoincrement",
-                "name text",
-                "description text\n"
+                ("id", "integer"),
+                ("name", "text"),
+                ("size range", "text"),
+                ("first name", "TEXT"),
+                ("last name", ""),
             ]
         );
-        assert_eq!(table, "oranges");
 
-        let input = "CREATE TABLE watermelon (id integer primary key, name text)";
-        let (_, (columns, table)) = parse_create_table(input)?;
-        assert_eq!(columns, vec!["id integer primary key", "name text"]);
-        assert_eq!(table, "watermelon");
+        Ok(())
+    }
 
-        let input = "CREATE TABLE \"superheroes\" (id integer primary key autoincrement, name text not null, eye_color text, hair_color text, appearance_count integer, first_appearance text, first_appearance_year text)";
-        let (_, (columns, table)) = parse_create_table(input)?;
+    #[test]
+    fn it_parses_column_types_and_constraints() -> TestResult {
+        let input = "create table if not exists main.'t' (\n\
+                a UNSIGNED BIG INT NOT NULL DEFAULT -1,\n\
+                b varchar(20, 2) COLLATE nocase UNIQUE ON CONFLICT REPLACE,\n\
+                c DEFAULT 'x,y' CHECK (c != 'z') CONSTRAINT fk REFERENCES p (id) ON DELETE SET NULL,\n\
+                d AS (a) STORED\n\
+            )";
+        let (remaining, def) = parse_create_table(input)?;
+        assert_eq!(remaining, "");
+        assert_eq!(def.name, "t");
         assert_eq!(
-            columns,
+            names(&def),
             vec![
-                "id integer primary key autoincrement",
-                "name text not null",
-                "eye_color text",
-                "hair_color text",
-                "appearance_count integer",
-                "first_appearance text",
-                "first_appearance_year text",
+                ("a", "UNSIGNED BIG INT"),
+                ("b", "varchar(20, 2)"),
+                ("c", ""),
+                ("d", ""),
             ]
         );
-        assert_eq!(table, "superheroes");
-
-        let input = "CREATE TABLE companies\n\
-            (\n\
-                id integer primary key autoincrement, name text, domain text, year_founded text, industry text, \"size range\" text, locality text, country text, current_employees text, total_employees text)";
-        let (_, (columns, table)) = parse_create_table(input)?;
         assert_eq!(
-            columns,
+            def.columns[0].constraints,
+            vec![
+                ColumnConstraint::NotNull,
+                ColumnConstraint::Default(int(-1))
+            ]
+        );
+        assert_eq!(
+            def.columns[1].constraints,
+            vec![
+                ColumnConstraint::Collate("nocase".into()),
+                ColumnConstraint::Unique,
+            ]
+        );
+        assert_eq!(
+            def.columns[2].constraints,
             vec![
-                "id integer primary key autoincrement",
-                "name text",
-                "domain text",
-                "year_founded text",
-                "industry text",
-                "\"size range\" text",
-                "locality text",
-                "country text",
-                "current_employees text",
-                "total_employees text",
+                ColumnConstraint::Default(text("x,y")),
+                ColumnConstraint::Check(Expr::binary(col("c"), BinaryOp::Ne, text("z"))),
+                ColumnConstraint::References(ForeignKey {
+                    table: "p".into(),
+                    columns: vec!["id".into()],
+                }),
             ]
         );
-        assert_eq!(table, "companies");
+        assert_eq!(
+            def.columns[3].constraints,
+            vec![ColumnConstraint::Generated {
+                expr: col("a"),
+                stored: true,
+            }]
+        );
 
         Ok(())
     }
 
     #[test]
-    fn it_parses_comma_separated_column_definitions() -> TestResult {
-        let input = "\n\
-                type text,\n\
-                name text\n\
-            ";
-        let (remaining, parsed) = parse_comma_separated_col_defs(input)?;
+    fn it_parses_table_constraints_and_options() -> TestResult {
+        let input = "CREATE TABLE t (id INTEGER, k TEXT, PRIMARY KEY (id DESC), \
+            CONSTRAINT u UNIQUE (k COLLATE nocase, id) CHECK (id > 0) \
+            FOREIGN KEY (k) REFERENCES p DEFERRABLE INITIALLY DEFERRED) WITHOUT ROWID, STRICT";
+        let (remaining, def) = parse_create_table(input)?;
+        let indexed = |name: &str, collation: Option<&str>, desc| IndexedColumn {
+            name: name.into(),
+            collation: collation.map(String::from),
+            desc,
+        };
         assert_eq!(remaining, "");
-        assert_eq!(parsed, vec!["type text", "name text\n"]);
-
-        let input = "\n tbl_name text,rootpage  integer";
-        let (remaining, parsed) = parse_comma_separated_col_defs(input)?;
-        assert_eq!(remaining, "");
-        assert_eq!(parsed, vec!["tbl_name text", "rootpage  integer"]);
+        assert_eq!(
+            def.constraints,
+            vec![
+                TableConstraint::PrimaryKey(vec![indexed("id", None, true)]),
+                TableConstraint::Unique(vec![
+                    indexed("k", Some("nocase"), false),
+                    indexed("id", None, false),
+                ]),
+                TableConstraint::Check(Expr::binary(col("id"), BinaryOp::Gt, int(0))),
+                TableConstraint::ForeignKey {
+                    columns: vec!["k".into()],
+                    references: ForeignKey {
+                        table: "p".into(),
+                        columns: vec![],
+                    },
+                },
+            ]
+        );
+        assert!(def.without_rowid && def.strict);
+        assert_eq!(def.primary_key(), vec![indexed("id", None, true)]);
+        assert_eq!(def.rowid_alias(), None);
 
-        let input = "id integer primary key autoincrement,\n\tname  text";
-        let (remaining, parsed) = parse_comma_separated_col_defs(input)?;
-        assert_eq!(remaining, "");
+        let (_, def) = parse_create_table(
+            "create table t (a, b collate nocase, c, primary key (c, b)) without rowid",
+        )?;
         assert_eq!(
-            parsed,
-            vec!["id integer primary key autoincrement", "name  text"]
+            def.primary_key(),
+            vec![
+                indexed("c", None, false),
+                indexed("b", Some("nocase"), false)
+            ]
         );
+        assert_eq!(def.record_positions(), vec![2, 1, 0]);
 
-        let input = "id integer, \"size range\" text";
-        let (remaining, parsed) = parse_comma_separated_col_defs(input)?;
-        assert_eq!(remaining, "");
-        assert_eq!(parsed, vec!["id integer", "\"size range\" text"]);
+        let (_, def) = parse_create_table("create table t (ID integer, primary key (id))")?;
+        assert_eq!(def.rowid_alias(), Some(0));
+
+        let (_, def) = parse_create_table("create table t (id int primary key)")?;
+        assert_eq!(def.rowid_alias(), None);
+
+        let (_, def) = parse_create_table("create table t (id integer(10) primary key)")?;
+        assert_eq!(def.rowid_alias(), None);
+
+        let (_, def) = parse_create_table("create table t (id INTEGER PRIMARY KEY DESC)")?;
+        assert_eq!(def.rowid_alias(), None);
+
+        let (_, def) = parse_create_table("create table t (id Integer, primary key (id desc))")?;
+        assert_eq!(def.rowid_alias(), Some(0));
 
         Ok(())
     }
 
+    fn columns(names: &[&str]) -> Vec<ResultColumn> {
+        names.iter().map(|n| result(col(n))).collect()
+    }
+
+    fn result(expr: Expr) -> ResultColumn {
+        ResultColumn::Expr { expr, alias: None }
+    }
+
+    fn count_all() -> Expr {
+        Expr::Function {
+            name: "count".into(),
+            args: vec![],
+            distinct: false,
+        }
+    }
+
     #[test]
     fn it_parses_select_sentences() -> TestResult {
         let input = "SELECT name, producer FROM apples";
-        let (_, (columns, table, _)) = parse_select(input)?;
-        assert_eq!(columns, vec!["name", "producer"]);
-        assert_eq!(table, "apples");
+        let (_, select) = parse_select(input)?;
+        assert_eq!(select.columns, columns(&["name", "producer"]));
+        assert_eq!(
+            select.from[0].table,
+            TableOrSubquery::Table("apples".into())
+        );
 
         let input = "SELECT * FROM oranges";
-        let (_, (columns, table, _)) = parse_select(input)?;
-        assert_eq!(columns, vec!["*"]);
-        assert_eq!(table, "oranges");
+        let (_, select) = parse_select(input)?;
+        assert_eq!(select.columns, vec![ResultColumn::All]);
+        assert_eq!(
+            select.from[0].table,
+            TableOrSubquery::Table("oranges".into())
+        );
 
         let input = "SELECT name, foo_bar FROM grapes";
-        let (_, (columns, table, _)) = parse_select(input)?;
-        assert_eq!(columns, vec!["name", "foo_bar"]);
-        assert_eq!(table, "grapes");
+        let (_, select) = parse_select(input)?;
+        assert_eq!(select.columns, columns(&["name", "foo_bar"]));
+        assert_eq!(
+            select.from[0].table,
+            TableOrSubquery::Table("grapes".into())
+        );
 
         let input = "SELECT count(*) FROM grapes";
-        let (_, (columns, table, _)) = parse_select(input)?;
-        assert_eq!(columns, vec!["count(*)"]);
-        assert_eq!(table, "grapes");
+        let (_, select) = parse_select(input)?;
+        assert_eq!(select.columns, vec![result(count_all())]);
+        assert_eq!(
+            select.from[0].table,
+            TableOrSubquery::Table("grapes".into())
+        );
 
         let input = "SELECT name, color FROM apples WHERE color = 'Yellow'";
-        let (_, (columns, table, conditions)) = parse_select(input)?;
-        assert_eq!(columns, vec!["name", "color"]);
-        assert_eq!(table, "apples");
-        assert_eq!(conditions, vec![("color", "Yellow")]);
+        let (_, select) = parse_select(input)?;
+        assert_eq!(select.columns, columns(&["name", "color"]));
+        assert_eq!(
+            select.from[0].table,
+            TableOrSubquery::Table("apples".into())
+        );
+        assert_eq!(
+            select.conditions,
+            Conditions(Some(Expr::binary(
+                col("color"),
+                BinaryOp::Eq,
+                text("Yellow")
+            )))
+        );
 
         let input = "SELECT id, name FROM superheroes WHERE eye_color = 'Pink Eyes'";
-        let (_, (columns, table, conditions)) = parse_select(input)?;
-        assert_eq!(columns, vec!["id", "name"]);
-        assert_eq!(table, "superheroes");
-        assert_eq!(conditions, vec![("eye_color", "Pink Eyes")]);
+        let (_, select) = parse_select(input)?;
+        assert_eq!(select.columns, columns(&["id", "name"]));
+        assert_eq!(
+            select.from[0].table,
+            TableOrSubquery::Table("superheroes".into())
+        );
+        assert_eq!(
+            select.conditions,
+            Conditions(Some(Expr::binary(
+                col("eye_color"),
+                BinaryOp::Eq,
+                text("Pink Eyes")
+            )))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_insert_sentences() -> TestResult {
+        let input = "INSERT INTO apples (name, color) VALUES ('Fuji', 'Red'), ('Gala', NULL);";
+        let (remaining, insert) = parse_insert(input)?;
+        assert_eq!(remaining, ";");
+        assert_eq!(
+            insert,
+            Insert {
+                table: "apples",
+                columns: vec!["name".into(), "color".into()],
+                rows: vec![
+                    vec![text("Fuji"), text("Red")],
+                    vec![text("Gala"), Expr::Literal(Literal::Null)],
+                ],
+            }
+        );
+
+        let input = "insert into apples values(7, 'Envy',\n'Red')";
+        let (remaining, insert) = parse_insert(input)?;
+        assert_eq!(remaining, "");
+        assert!(insert.columns.is_empty());
+        assert_eq!(insert.rows, vec![vec![int(7), text("Envy"), text("Red")]]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_group_by_and_having() -> TestResult {
+        let input =
+            "SELECT color, count(*) FROM apples GROUP BY color HAVING count(*) > 1 ORDER BY 2";
+        let (remaining, select) = parse_select(input)?;
+        assert_eq!(remaining, "");
+        assert_eq!(select.group_by, vec![col("color")]);
+        assert_eq!(
+            select.having,
+            Some(Expr::binary(count_all(), BinaryOp::Gt, int(1)))
+        );
+        assert_eq!(select.order_by.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_function_calls() -> TestResult {
+        let (_, expr) = parse_expr("COUNT ( * )")?;
+        assert_eq!(expr, count_all());
+
+        let (_, expr) = parse_expr("count(distinct color) >= 2")?;
+        assert_eq!(
+            expr,
+            Expr::binary(
+                Expr::Function {
+                    name: "count".into(),
+                    args: vec![col("color")],
+                    distinct: true,
+                },
+                BinaryOp::Ge,
+                int(2),
+            )
+        );
+
+        let (_, expr) = parse_expr("group_concat(name, '-')")?;
+        assert_eq!(
+            expr,
+            Expr::Function {
+                name: "group_concat".into(),
+                args: vec![col("name"), text("-")],
+                distinct: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_order_by_and_limit() -> TestResult {
+        let input = "SELECT name FROM apples WHERE id > 1 ORDER BY color DESC, name nulls last LIMIT 2 OFFSET 1;";
+        let (remaining, select) = parse_select(input)?;
+        assert_eq!(remaining, ";");
+        assert_eq!(
+            select.conditions,
+            Conditions(Some(Expr::binary(col("id"), BinaryOp::Gt, int(1))))
+        );
+        assert_eq!(
+            select.order_by,
+            vec![
+                OrderingTerm {
+                    expr: col("color"),
+                    desc: true,
+                    nulls_first: false,
+                },
+                OrderingTerm {
+                    expr: col("name"),
+                    desc: false,
+                    nulls_first: false,
+                },
+            ]
+        );
+        assert_eq!(
+            select.limit,
+            Limit {
+                count: Some(2),
+                offset: 1,
+            }
+        );
+
+        let input = "SELECT name FROM apples order by 1 asc limit 3, 10";
+        let (_, select) = parse_select(input)?;
+        assert_eq!(
+            select.order_by,
+            vec![OrderingTerm {
+                expr: int(1),
+                desc: false,
+                nulls_first: true,
+            }]
+        );
+        assert_eq!(
+            select.limit,
+            Limit {
+                count: Some(10),
+                offset: 3,
+            }
+        );
+
+        let input = "SELECT name FROM apples limit -1";
+        let (_, select) = parse_select(input)?;
+        assert_eq!(
+            select.limit,
+            Limit {
+                count: None,
+                offset: 0,
+            }
+        );
 
         Ok(())
     }
@@ -370,31 +1615,149 @@ mod tests {
     #[test]
     fn it_parses_comma_separated_string() -> TestResult {
         let input = "foo,bar,baz";
-        let (remaining, parsed) = parse_comma_separated_col_or_funcs(input)?;
+        let (remaining, parsed) = parse_result_columns(input)?;
         assert_eq!(remaining, "");
-        assert_eq!(parsed, vec!["foo", "bar", "baz"]);
+        assert_eq!(parsed, columns(&["foo", "bar", "baz"]));
 
         let input = " foo ,\nbar\n, baz ";
-        let (remaining, parsed) = parse_comma_separated_col_or_funcs(input)?;
-        assert_eq!(remaining, "");
-        assert_eq!(parsed, vec!["foo", "bar", "baz"]);
+        let (remaining, parsed) = parse_result_columns(input)?;
+        assert_eq!(remaining.trim(), "");
+        assert_eq!(parsed, columns(&["foo", "bar", "baz"]));
 
         let input = " foo ";
-        let (remaining, parsed) = parse_comma_separated_col_or_funcs(input)?;
-        assert_eq!(remaining, "");
-        assert_eq!(parsed, vec!["foo"]);
+        let (remaining, parsed) = parse_result_columns(input)?;
+        assert_eq!(remaining.trim(), "");
+        assert_eq!(parsed, columns(&["foo"]));
 
         let input = "*";
-        let (remaining, parsed) = parse_comma_separated_col_or_funcs(input)?;
+        let (remaining, parsed) = parse_result_columns(input)?;
+        assert_eq!(remaining, "");
+        assert_eq!(parsed, vec![ResultColumn::All]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_aliases_and_qualified_columns() -> TestResult {
+        let aliased = |expr, alias: &str| ResultColumn::Expr {
+            expr,
+            alias: Some(alias.into()),
+        };
+        let qualified = |table: &str, name: &str| Expr::Column {
+            table: Some(table.into()),
+            name: name.into(),
+        };
+
+        let input =
+            "SELECT apples.*, name AS n, count(*) total, Apples . id \"the id\" FROM apples";
+        let (_, select) = parse_select(input)?;
+        assert_eq!(
+            select.columns,
+            vec![
+                ResultColumn::TableAll("apples".into()),
+                aliased(col("name"), "n"),
+                aliased(count_all(), "total"),
+                aliased(qualified("Apples", "id"), "the id"),
+            ]
+        );
+
+        let (remaining, parsed) = parse_result_column("[my table].* from")?;
+        assert_eq!(remaining, "from");
+        assert_eq!(parsed, ResultColumn::TableAll("my table".into()));
+
+        assert!(parse_select("SELECT name AS FROM apples").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_subqueries() -> TestResult {
+        let input = "SELECT (SELECT count(*) FROM oranges) FROM apples \
+            WHERE id IN (SELECT id FROM oranges) AND NOT EXISTS (SELECT 1 FROM pears)";
+        let (remaining, select) = parse_select(input)?;
         assert_eq!(remaining, "");
-        assert_eq!(parsed, vec!["*"]);
+        let ResultColumn::Expr {
+            expr: Expr::Subquery(subquery),
+            ..
+        } = &select.columns[0]
+        else {
+            panic!("expected a subquery in {:?}", select.columns);
+        };
+        assert_eq!(subquery.columns, vec![result(count_all())]);
+
+        let conditions = select.conditions.0.unwrap();
+        let [Expr::InSelect { expr, .. }, Expr::Not(exists)] = conditions.conjuncts()[..] else {
+            panic!("expected IN and NOT EXISTS in {conditions:?}");
+        };
+        assert_eq!(**expr, col("id"));
+        assert!(matches!(exists.as_ref(), Expr::Exists(_)));
+
+        let input = "SELECT t.n FROM ( SELECT name n FROM apples ) AS t JOIN (SELECT 1 FROM x) y";
+        let (_, select) = parse_select(input)?;
+        assert!(matches!(
+            &select.from[0].table,
+            TableOrSubquery::Subquery(subquery) if subquery.from[0].table == TableOrSubquery::Table("apples".into())
+        ));
+        assert_eq!(select.from[0].alias, Some("t".into()));
+        assert_eq!(select.from[1].alias, Some("y".into()));
+        Ok(())
+    }
 
+    #[test]
+    fn it_parses_compound_selects() -> TestResult {
+        let input = "SELECT id FROM a UNION ALL SELECT id FROM b WHERE id > 1 \
+            EXCEPT SELECT id FROM c ORDER BY 1 LIMIT 2";
+        let (remaining, select) = parse_select(input)?;
+        assert_eq!(remaining, "");
+        let ops: Vec<CompoundOp> = select.compound.iter().map(|arm| arm.op).collect();
+        assert_eq!(ops, vec![CompoundOp::UnionAll, CompoundOp::Except]);
+        assert!(select.compound[0].select.conditions.0.is_some());
+        assert_eq!(select.order_by.len(), 1);
+        assert_eq!(select.limit.count, Some(2));
+        assert!(select
+            .compound
+            .iter()
+            .all(|arm| arm.select.order_by.is_empty()));
+
+        let (remaining, _) = parse_select("SELECT id FROM a ORDER BY id UNION SELECT id FROM b")?;
+        assert_eq!(remaining, " UNION SELECT id FROM b");
+        assert!(matches!(
+            parse_select("SELECT id FROM a INTERSECT id FROM b"),
+            Err(Err::Failure(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn it_parses_with_clauses() -> TestResult {
+        let input = "WITH RECURSIVE t(n) AS (SELECT id FROM a UNION ALL SELECT n FROM t LIMIT 3), \
+            u AS MATERIALIZED (SELECT n FROM t) SELECT n FROM u";
+        let (remaining, select) = parse_select(input)?;
+        assert_eq!(remaining, "");
+        let [t, u] = &select.with[..] else {
+            panic!("expected two tables in {:?}", select.with);
+        };
+        assert_eq!(t.name, "t");
+        assert_eq!(t.columns, vec!["n".to_string()]);
+        let [arm] = &t.select.compound[..] else {
+            panic!("expected a compound select in {:?}", t.select);
+        };
+        assert_eq!(arm.op, CompoundOp::UnionAll);
+        assert_eq!(arm.select.from[0].table, TableOrSubquery::Table("t".into()));
+        assert_eq!(t.select.limit.count, Some(3));
+        assert_eq!(u.name, "u");
+        assert!(u.select.compound.is_empty());
+        assert_eq!(select.from[0].table, TableOrSubquery::Table("u".into()));
+
+        assert!(matches!(
+            parse_select("WITH t AS SELECT 1 FROM a SELECT * FROM t"),
+            Err(Err::Failure(_))
+        ));
         Ok(())
     }
 
     #[test]
     fn it_trims_string() -> TestResult {
-        let mut parser = trim(parse_col_or_funcs);
+        let mut parser = trim(parse_table_name);
         let input = "\n foo\t\r";
         let (remaining, parsed) = parser.parse(input)?;
         assert_eq!(remaining, "");
@@ -415,30 +1778,30 @@ mod tests {
 
     #[test]
     fn it_parses_identifiers() -> TestResult {
-        let input = "foobar baz";
-        let (remaining, parsed) = parse_col_or_funcs(input)?;
-        assert_eq!(remaining, " baz");
-        assert_eq!(parsed, "foobar");
+        let input = "foobar from";
+        let (remaining, parsed) = parse_result_column(input)?;
+        assert_eq!(remaining, " from");
+        assert_eq!(parsed, result(col("foobar")));
 
         let input = "* baz";
-        let (remaining, parsed) = parse_col_or_funcs(input)?;
-        assert_eq!(remaining, " baz");
-        assert_eq!(parsed, "*");
+        let (remaining, parsed) = parse_result_column(input)?;
+        assert_eq!(remaining, "baz");
+        assert_eq!(parsed, ResultColumn::All);
 
-        let input = "foo* baz";
-        let (remaining, parsed) = parse_col_or_funcs(input)?;
-        assert_eq!(remaining, " baz");
-        assert_eq!(parsed, "foo*");
+        let input = "count(*) from";
+        let (remaining, parsed) = parse_result_column(input)?;
+        assert_eq!(remaining, "from");
+        assert_eq!(parsed, result(count_all()));
 
-        let input = "foo_bar baz";
-        let (remaining, parsed) = parse_col_or_funcs(input)?;
-        assert_eq!(remaining, " baz");
-        assert_eq!(parsed, "foo_bar");
+        let input = "foo_bar Order";
+        let (remaining, parsed) = parse_result_column(input)?;
+        assert_eq!(remaining, " Order");
+        assert_eq!(parsed, result(col("foo_bar")));
 
         let input = "foo_bar";
-        let (remaining, parsed) = parse_col_or_funcs(input)?;
+        let (remaining, parsed) = parse_result_column(input)?;
         assert_eq!(remaining, "");
-        assert_eq!(parsed, "foo_bar");
+        assert_eq!(parsed, result(col("foo_bar")));
 
         Ok(())
     }
@@ -0,0 +1,646 @@
+use super::{
+    aggregate::{Groups, Key},
+    cte::{CommonTableExpr, Ctes},
+    expr::{Expr, Literal, Row},
+    join::{JoinedRow, Outer, Sources},
+    Affinity, Conditions, Db, Error, RecordValue, Result,
+};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::io::{Read, Seek};
+
+/// Names given to result columns with `AS`, and their expressions.
+type Aliases = Vec<(String, Expr)>;
+
+/// A `SELECT` statement. With compound operators, the ordering terms and the
+/// limit apply to the rows of the compound, while the selects after the
+/// operators have none of their own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub with: Vec<CommonTableExpr>,
+    pub columns: Vec<ResultColumn>,
+    pub from: Vec<FromTable>,
+    pub conditions: Conditions,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    pub compound: Vec<Compound>,
+    pub order_by: Vec<OrderingTerm>,
+    pub limit: Limit,
+}
+
+/// One item of the projection, where `*` stands for every column and
+/// `table.*` for every column of the table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultColumn {
+    All,
+    TableAll(String),
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+/// A table of the `FROM` clause, whose columns are qualified by its alias or
+/// else by its name, and how it is joined with the tables before it. The
+/// first table has no constraint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FromTable {
+    pub table: TableOrSubquery,
+    pub alias: Option<String>,
+    pub join: JoinKind,
+    pub constraint: JoinConstraint,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableOrSubquery {
+    Table(String),
+    /// A derived table, whose rows are those of the query.
+    Subquery(Box<Select>),
+}
+
+/// A select combined with the rows of the selects before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compound {
+    pub op: CompoundOp,
+    pub select: Select,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompoundOp {
+    Union,
+    UnionAll,
+    Intersect,
+    Except,
+}
+
+impl CompoundOp {
+    /// Combines the rows. Except for `UNION ALL`, which appends them, the
+    /// result has no duplicate rows and is in sort order, like SQLite returns
+    /// it from the temporary index it builds.
+    pub(super) fn apply(
+        &self,
+        left: Vec<Vec<RecordValue>>,
+        right: Vec<Vec<RecordValue>>,
+    ) -> Vec<Vec<RecordValue>> {
+        let distinct = |rows: Vec<Vec<RecordValue>>| rows.into_iter().map(Key).collect();
+        let (left, right): (BTreeSet<Key>, BTreeSet<Key>) = match self {
+            Self::UnionAll => return [left, right].concat(),
+            _ => (distinct(left), distinct(right)),
+        };
+        let rows: Vec<Key> = match self {
+            Self::Intersect => left.intersection(&right).cloned().collect(),
+            Self::Except => left.difference(&right).cloned().collect(),
+            _ => left.union(&right).cloned().collect(),
+        };
+        rows.into_iter().map(|Key(row)| row).collect()
+    }
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Union => "UNION",
+            Self::UnionAll => "UNION ALL",
+            Self::Intersect => "INTERSECT",
+            Self::Except => "EXCEPT",
+        }
+    }
+}
+
+/// `,`, `JOIN`, `INNER JOIN` and `CROSS JOIN` all make inner joins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JoinConstraint {
+    None,
+    On(Expr),
+    Using(Vec<String>),
+}
+
+/// The rows of a query with the name and affinity of each column, which is
+/// how a query in `FROM` is seen as a table.
+#[derive(Debug, Default, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<(String, Affinity)>,
+    pub rows: Vec<Vec<RecordValue>>,
+}
+
+impl ResultSet {
+    fn names(&self) -> Vec<String> {
+        self.columns.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Position of the first column with the name.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|(col, _)| col.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Select {
+    /// Runs the statement and returns the values of each result row.
+    pub fn execute<R: Read + Seek>(&self, db: &Db<R>) -> Result<Vec<Vec<RecordValue>>> {
+        self.query(db, &Ctes::default(), None)
+            .map(|result| result.rows)
+    }
+
+    /// Runs the statement, which can read from the tables of the `WITH`
+    /// clauses it is nested in, as a subquery of the outer query when given
+    /// one.
+    ///
+    /// Every column the statement refers to outside of its subqueries is
+    /// resolved to its table before any row is read, so that a misspelt name
+    /// is an error even on an empty table.
+    pub(super) fn query<R: Read + Seek>(
+        &self,
+        db: &Db<R>,
+        ctes: &Ctes,
+        outer: Option<Outer<'_, R>>,
+    ) -> Result<ResultSet> {
+        let ctes = ctes.with(db, &self.with, outer)?;
+        if self.compound.is_empty() {
+            return self.query_core(db, ctes, outer, &self.order_by, self.limit);
+        }
+
+        let mut result = self.query_core(db, ctes.clone(), outer, &[], Limit::default())?;
+        let mut names = vec![result.names()];
+        for arm in &self.compound {
+            let rows = arm.select.query(db, &ctes, outer)?;
+            if rows.columns.len() != result.columns.len() {
+                return Err(err!(
+                    "SELECTs to the left and right of {} do not have the same number of result columns",
+                    arm.op.keyword()
+                ));
+            }
+            names.push(rows.names());
+            result.rows = arm.op.apply(result.rows, rows.rows);
+        }
+
+        let order_by = self.compound_order_by(&names)?;
+        if !order_by.is_empty() {
+            result.rows.sort_by(|a, b| {
+                order_by
+                    .iter()
+                    .map(|(idx, term)| term.compare(&a[*idx], &b[*idx]))
+                    .find(|o| o.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        result.rows = self.limit.apply(result.rows);
+        Ok(result)
+    }
+
+    /// Runs the select without its compound operators, sorted by the
+    /// ordering terms and limited by the limit.
+    fn query_core<R: Read + Seek>(
+        &self,
+        db: &Db<R>,
+        ctes: Ctes,
+        outer: Option<Outer<'_, R>>,
+        order_by: &[OrderingTerm],
+        limit: Limit,
+    ) -> Result<ResultSet> {
+        let sources = Sources::open(db, &self.from, ctes, outer)?;
+
+        let (columns, names, aliases) = self.result_columns(&sources)?;
+        // Aliases stand for their expression wherever no column has the name.
+        let resolve = |expr: &Expr| sources.resolve(expr, |name| alias(&aliases, name).cloned());
+        let conditions = Conditions(self.conditions.0.as_ref().map(resolve).transpose()?);
+        let group_by = self
+            .group_by
+            .iter()
+            .map(resolve)
+            .collect::<Result<Vec<Expr>>>()?;
+        let having = self.having.as_ref().map(resolve).transpose()?;
+        let order_by = resolve_order_by(order_by, &columns, &aliases, resolve)?;
+
+        let aggregates: Vec<&Expr> = columns
+            .iter()
+            .chain(&having)
+            .chain(order_by.iter().map(|term| &term.expr))
+            .flat_map(Expr::aggregates)
+            .collect();
+
+        let outputs = if aggregates.is_empty() && group_by.is_empty() && having.is_none() {
+            // Without sorting, rows past the limit are never needed.
+            let stop_at = match limit.count {
+                Some(n) if order_by.is_empty() => Some(limit.offset + n),
+                _ => None,
+            };
+
+            let mut rows = vec![];
+            if stop_at != Some(0) {
+                sources.join(&conditions, &mut |row| {
+                    if conditions.satisfy(&row)? {
+                        rows.push(row);
+                    }
+                    Ok(!stop_at.is_some_and(|n| rows.len() >= n))
+                })?;
+            }
+            project(rows, &columns, &order_by)?
+        } else {
+            let mut groups = Groups::new(&group_by, aggregates)?;
+            sources.join(&conditions, &mut |row| {
+                if conditions.satisfy(&row)? {
+                    groups.add(row)?;
+                }
+                Ok(true)
+            })?;
+
+            let mut rows = vec![];
+            for row in groups.into_rows(JoinedRow::empty(&sources))? {
+                if let Some(having) = &having {
+                    if !having.is_true(&row)? {
+                        continue;
+                    }
+                }
+                rows.push(row);
+            }
+            project(rows, &columns, &order_by)?
+        };
+
+        Ok(ResultSet {
+            columns: names
+                .into_iter()
+                .zip(&columns)
+                .map(|(name, expr)| (name, sources.affinity(expr)))
+                .collect(),
+            rows: limit.apply(outputs),
+        })
+    }
+
+    /// The expressions of the result columns, with `*` and `table.*`
+    /// expanded to the columns of the tables, their names and the aliases
+    /// given to them.
+    ///
+    /// A column is named by its alias, or else by the name of the table
+    /// column it is. Other expressions are named `columnN` by their position.
+    fn result_columns<R: Read + Seek>(
+        &self,
+        sources: &Sources<'_, R>,
+    ) -> Result<(Vec<Expr>, Vec<String>, Aliases)> {
+        let mut columns = vec![];
+        let mut names = vec![];
+        let mut aliases = vec![];
+
+        for col in &self.columns {
+            let (exprs, alias) = match col {
+                ResultColumn::All => (sources.all_columns(None)?, None),
+                ResultColumn::TableAll(table) => (sources.all_columns(Some(table))?, None),
+                ResultColumn::Expr { expr, alias } => {
+                    let expr = sources.resolve(expr, |_| None)?;
+                    if let Some(alias) = alias {
+                        aliases.push((alias.clone(), expr.clone()));
+                    }
+                    (vec![expr], alias.as_ref())
+                }
+            };
+
+            for expr in exprs {
+                names.push(match (alias, &expr) {
+                    (Some(alias), _) => alias.clone(),
+                    (None, Expr::Column { name, .. }) => name.clone(),
+                    (None, _) => format!("column{}", names.len() + 1),
+                });
+                columns.push(expr);
+            }
+        }
+
+        Ok((columns, names, aliases))
+    }
+
+    /// The position of the result column each `ORDER BY` term of a compound
+    /// select sorts by, which is given as an integer or else by a column
+    /// named like a result column of one of the selects, the first one
+    /// first.
+    fn compound_order_by(&self, names: &[Vec<String>]) -> Result<Vec<(usize, &OrderingTerm)>> {
+        let count = names[0].len();
+        self.order_by
+            .iter()
+            .enumerate()
+            .map(|(i, term)| {
+                let idx = match &term.expr {
+                    Expr::Literal(Literal::Integer(n)) => {
+                        position(*n, count).ok_or_else(|| out_of_range(i, count))?
+                    }
+                    Expr::Column { name, .. } => names
+                        .iter()
+                        .find_map(|arm| arm.iter().position(|col| col.eq_ignore_ascii_case(name)))
+                        .ok_or_else(|| {
+                            err!(
+                                "{} ORDER BY term does not match any column in the result set",
+                                ordinal(i + 1)
+                            )
+                        })?,
+                    _ => {
+                        return Err(Error::Unsupported(
+                            "ORDER BY expressions of a compound select".to_string(),
+                        ))
+                    }
+                };
+                Ok((idx, term))
+            })
+            .collect()
+    }
+}
+
+/// The `ORDER BY` terms where an integer refers to a result column by its
+/// position. A term naming an alias refers to its result column even when
+/// a table column has the name.
+fn resolve_order_by(
+    order_by: &[OrderingTerm],
+    columns: &[Expr],
+    aliases: &[(String, Expr)],
+    resolve: impl Fn(&Expr) -> Result<Expr>,
+) -> Result<Vec<OrderingTerm>> {
+    let mut order_by = order_by.to_vec();
+
+    for (i, term) in order_by.iter_mut().enumerate() {
+        term.expr = match &term.expr {
+            Expr::Literal(Literal::Integer(n)) => position(*n, columns.len())
+                .map(|idx| columns[idx].clone())
+                .ok_or_else(|| out_of_range(i, columns.len()))?,
+            expr => match expr {
+                Expr::Column { table: None, name } => alias(aliases, name).cloned(),
+                _ => None,
+            }
+            .map_or_else(|| resolve(expr), Ok)?,
+        };
+    }
+
+    Ok(order_by)
+}
+
+/// The index of the `n`th of `count` result columns.
+fn position(n: i64, count: usize) -> Option<usize> {
+    usize::try_from(n)
+        .ok()
+        .and_then(|n| n.checked_sub(1))
+        .filter(|&idx| idx < count)
+}
+
+fn out_of_range(i: usize, count: usize) -> Error {
+    err!(
+        "{} ORDER BY term out of range - should be between 1 and {count}",
+        ordinal(i + 1)
+    )
+}
+
+fn alias<'e>(aliases: &'e [(String, Expr)], name: &str) -> Option<&'e Expr> {
+    aliases
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map(|(_, expr)| expr)
+}
+
+/// Evaluates the result columns of each row, sorted by the ordering terms.
+fn project<T: Row>(
+    rows: Vec<T>,
+    columns: &[Expr],
+    order_by: &[OrderingTerm],
+) -> Result<Vec<Vec<RecordValue>>> {
+    let mut keyed = vec![];
+    for row in rows {
+        let keys = order_by
+            .iter()
+            .map(|term| term.expr.eval(&row))
+            .collect::<Result<Vec<RecordValue>>>()?;
+        let values = columns
+            .iter()
+            .map(|col| col.eval(&row))
+            .collect::<Result<Vec<RecordValue>>>()?;
+        keyed.push((keys, values));
+    }
+
+    if !order_by.is_empty() {
+        keyed.sort_by(|(a, _), (b, _)| compare_keys(order_by, a, b));
+    }
+    Ok(keyed.into_iter().map(|(_, values)| values).collect())
+}
+
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (1, 11) | (2, 12) | (3, 13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{n}{suffix}")
+}
+
+fn compare_keys(terms: &[OrderingTerm], a: &[RecordValue], b: &[RecordValue]) -> Ordering {
+    terms
+        .iter()
+        .zip(a.iter().zip(b))
+        .map(|(term, (a, b))| term.compare(a, b))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// One `expr [ASC|DESC] [NULLS FIRST|LAST]` key of an `ORDER BY` clause.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingTerm {
+    pub expr: Expr,
+    pub desc: bool,
+    pub nulls_first: bool,
+}
+
+impl OrderingTerm {
+    fn compare(&self, a: &RecordValue, b: &RecordValue) -> Ordering {
+        match (a, b) {
+            (RecordValue::Null, RecordValue::Null) => Ordering::Equal,
+            (RecordValue::Null, _) if self.nulls_first => Ordering::Less,
+            (RecordValue::Null, _) => Ordering::Greater,
+            (_, RecordValue::Null) if self.nulls_first => Ordering::Greater,
+            (_, RecordValue::Null) => Ordering::Less,
+            _ if self.desc => b.cmp(a),
+            _ => a.cmp(b),
+        }
+    }
+}
+
+/// `LIMIT` and `OFFSET` of a statement, where no count means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limit {
+    pub count: Option<usize>,
+    pub offset: usize,
+}
+
+impl Limit {
+    pub(super) fn apply<T>(&self, rows: Vec<T>) -> Vec<T> {
+        let rows = rows.into_iter().skip(self.offset);
+        match self.count {
+            Some(n) => rows.take(n).collect(),
+            None => rows.collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{sql::query, Error, Result};
+
+    #[test]
+    fn it_limits_rows_from_an_offset() -> Result<()> {
+        assert_eq!(
+            query("select id, name from apples order by id limit 2 offset 1")?,
+            vec!["2|Fuji", "3|Honeycrisp"]
+        );
+        assert_eq!(query("select id from apples limit 1, 2")?, vec!["2", "3"]);
+        assert_eq!(
+            query("select id from apples limit 0")?,
+            Vec::<String>::new()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_takes_a_negative_limit_as_no_limit() -> Result<()> {
+        assert_eq!(
+            query("select id from apples order by id desc limit -1")?,
+            vec!["4", "3", "2", "1"]
+        );
+        assert_eq!(
+            query("select id from apples order by id limit -1 offset 2")?,
+            vec!["3", "4"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_places_nulls_first_or_last() -> Result<()> {
+        // The key is NULL for names after 'G' and 0 for the others.
+        let sorted = |order: &str| {
+            query(&format!(
+                "select name from apples order by name > 'G' and color = null {order}, name"
+            ))
+        };
+        let nulls_first = vec!["Golden Delicious", "Granny Smith", "Honeycrisp", "Fuji"];
+        let nulls_last = vec!["Fuji", "Golden Delicious", "Granny Smith", "Honeycrisp"];
+        assert_eq!(sorted("")?, nulls_first);
+        assert_eq!(sorted("nulls last")?, nulls_last);
+        assert_eq!(sorted("desc")?, nulls_last);
+        assert_eq!(sorted("desc nulls first")?, nulls_first);
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_subqueries_for_each_row() -> Result<()> {
+        assert_eq!(
+            query(
+                "select name, (select count(*) from oranges o where o.id < a.id) from apples a \
+                 where id > 2"
+            )?,
+            vec!["Honeycrisp|2", "Golden Delicious|3"]
+        );
+        assert_eq!(
+            query("select count(*), (select max(id) from oranges) from apples where id > 10")?,
+            vec!["0|6"]
+        );
+        assert!(query("select (select id, name from oranges) from apples")
+            .unwrap_err()
+            .to_string()
+            .ends_with("sub-select returns 2 columns - expected 1"));
+        Ok(())
+    }
+
+    #[test]
+    fn it_filters_rows_by_subqueries() -> Result<()> {
+        assert_eq!(
+            query(
+                "select id from oranges \
+                 where id in (select id from apples where color like '%red') order by id"
+            )?,
+            vec!["2", "3"]
+        );
+        assert_eq!(
+            query("select id from oranges where id not in (select id from apples)")?,
+            vec!["5", "6"]
+        );
+        assert_eq!(
+            query(
+                "select name from apples a \
+                 where exists (select 1 from oranges o where o.id = a.id and o.name like 'T%')"
+            )?,
+            vec!["Fuji", "Honeycrisp"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_reads_subqueries_in_from() -> Result<()> {
+        assert_eq!(
+            query(
+                "select a.name, t.count from apples a \
+                 join (select id, count(*) as count from oranges group by id) t using (id) \
+                 where id < 3"
+            )?,
+            vec!["Granny Smith|1", "Fuji|1"]
+        );
+        assert_eq!(
+            query("select * from (select max(id), name from oranges)")?,
+            vec!["6|Navel Orange"]
+        );
+        assert!(matches!(
+            query("select s.id from (select name from oranges) s"),
+            Err(Error::NoSuchColumn(name)) if name == "s.id"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn it_combines_compound_selects() -> Result<()> {
+        assert_eq!(
+            query("select id from apples union select id from oranges where id > 2")?,
+            vec!["1", "2", "3", "4", "5", "6"]
+        );
+        assert_eq!(
+            query(
+                "select id from oranges where id > 4 union all select id from apples where id < 3"
+            )?,
+            vec!["5", "6", "1", "2"]
+        );
+        assert_eq!(
+            query("select id from oranges intersect select id from apples where id > 1")?,
+            vec!["2", "3", "4"]
+        );
+        assert_eq!(
+            query("select id from oranges except select id from apples")?,
+            vec!["5", "6"]
+        );
+        assert_eq!(
+            query("select id from apples union all select id from oranges union select id from apples")?,
+            vec!["1", "2", "3", "4", "5", "6"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_sorts_and_limits_compound_selects() -> Result<()> {
+        assert_eq!(
+            query(
+                "select id, name from apples union select id, name from oranges \
+                 where id < 3 order by 1 desc, name limit 3 offset 1"
+            )?,
+            vec!["3|Honeycrisp", "2|Fuji", "2|Tangelo"]
+        );
+        assert_eq!(
+            query("select id a from apples union select id b from oranges order by b limit 2")?,
+            vec!["1", "2"]
+        );
+        assert!(
+            query("select id a from apples union select id b from oranges order by name")
+                .unwrap_err()
+                .to_string()
+                .contains("1st ORDER BY term does not match any column")
+        );
+        assert!(
+            query("select id from apples except select id, name from oranges")
+                .unwrap_err()
+                .to_string()
+                .contains("left and right of EXCEPT do not have the same number of result columns")
+        );
+        Ok(())
+    }
+}